@@ -0,0 +1,129 @@
+//! `#[db_test]`, re-exported by `diesel-database-tester` with the `macros`
+//! feature.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote, quote_spanned};
+use syn::{
+    parse::Parser, parse_macro_input, punctuated::Punctuated, spanned::Spanned, Expr, FnArg,
+    ItemFn, Meta, MetaNameValue, Path, Token, Type,
+};
+
+/// Turn a function taking the test database into a test: the database is
+/// created and migrated, handed to the function and dropped afterwards.
+///
+/// The single argument may be `&TestDb`, `Pool` or, with the `async`
+/// feature, `AsyncPool`. Async functions run on a fresh tokio runtime.
+/// `builder = path` names a `fn() -> TestDbBuilder` to start from instead of
+/// `TestDbBuilder::from_env()`; the database is named after the test either way.
+///
+/// ```ignore
+/// #[db_test]
+/// fn creates_todo(db: &TestDb) { ... }
+///
+/// #[db_test(builder = local_server)]
+/// async fn lists_todos(pool: AsyncPool) { ... }
+/// ```
+#[proc_macro_attribute]
+pub fn db_test(args: TokenStream, item: TokenStream) -> TokenStream {
+    let args = match Punctuated::<Meta, Token![,]>::parse_terminated.parse(args) {
+        Ok(args) => args,
+        Err(e) => return e.to_compile_error().into(),
+    };
+    let input = parse_macro_input!(item as ItemFn);
+    expand(args, input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+fn expand(args: Punctuated<Meta, Token![,]>, input: ItemFn) -> syn::Result<TokenStream2> {
+    let mut builder: Option<Path> = None;
+    for arg in args {
+        match arg {
+            Meta::NameValue(MetaNameValue {
+                path,
+                value: Expr::Path(value),
+                ..
+            }) if path.is_ident("builder") => builder = Some(value.path),
+            other => return Err(syn::Error::new(other.span(), "expected `builder = path`")),
+        }
+    }
+    let builder = match builder {
+        Some(path) => quote!(#path()),
+        None => quote!(::diesel_database_tester::TestDbBuilder::from_env()),
+    };
+
+    let ItemFn {
+        attrs,
+        vis,
+        sig,
+        block,
+    } = input;
+    let name = &sig.ident;
+    let inner = format_ident!("__{}_body", name);
+    let arg = match sig.inputs.len() {
+        0 => quote!(),
+        1 => injected(sig.inputs.first().unwrap())?,
+        _ => {
+            return Err(syn::Error::new(
+                sig.inputs.span(),
+                "#[db_test] functions take at most one argument",
+            ))
+        }
+    };
+    let inputs = &sig.inputs;
+    let output = &sig.output;
+    let asyncness = &sig.asyncness;
+    let call = if asyncness.is_some() {
+        quote! {
+            ::diesel_database_tester::__private::tokio::runtime::Builder::new_multi_thread()
+                .enable_all()
+                .build()
+                .expect("Failed to build the tokio runtime")
+                .block_on(#inner(#arg))
+        }
+    } else {
+        quote!(#inner(#arg))
+    };
+    Ok(quote! {
+        #[test]
+        #(#attrs)*
+        #vis fn #name() #output {
+            #asyncness fn #inner(#inputs) #output #block
+            let db = #builder
+                .named(concat!(module_path!(), "::", stringify!(#name)))
+                .build();
+            #call
+        }
+    })
+}
+
+/// The expression passed for the argument, depending on its type.
+fn injected(arg: &FnArg) -> syn::Result<TokenStream2> {
+    let FnArg::Typed(arg) = arg else {
+        return Err(syn::Error::new(
+            arg.span(),
+            "#[db_test] can't inject `self`",
+        ));
+    };
+    let (reference, ty) = match &*arg.ty {
+        Type::Reference(reference) => (true, &*reference.elem),
+        ty => (false, ty),
+    };
+    let last = match ty {
+        Type::Path(path) => path.path.segments.last().map(|s| s.ident.to_string()),
+        _ => None,
+    };
+    let span = arg.ty.span();
+    Ok(match (reference, last.as_deref()) {
+        (true, Some("TestDb")) => quote_spanned!(span=> &db),
+        (false, Some("Pool")) => quote_spanned!(span=> db.pool()),
+        (false, Some("AsyncPool")) => quote_spanned!(span=> db.async_pool()),
+        _ => {
+            return Err(syn::Error::new(
+                span,
+                "#[db_test] can inject `&TestDb`, `Pool` or `AsyncPool`",
+            ))
+        }
+    })
+}
@@ -0,0 +1,203 @@
+//! A login role per test database holding only the privileges the
+//! application has in production, so tests catch missing grants a superuser
+//! connection would hide.
+
+use diesel::{
+    dsl, select,
+    sql_types::{Bool, Text},
+    PgConnection, QueryResult, RunQueryDsl,
+};
+
+use crate::{
+    diagnostics,
+    sql::{self, quote_ident, quote_literal},
+};
+
+/// Tags the roles created for test databases, so nothing drops a role that
+/// merely shares a database's name.
+const COMMENT: &str = "diesel-database-tester app role";
+
+/// What the role the test's connections use may do, set with
+/// [`TestDbBuilder::app_role`](crate::TestDbBuilder::app_role).
+///
+/// The role is named like the database, can only connect to it and is
+/// dropped with it. Setup, fixtures, seeds and the other helpers of
+/// [`TestDb`](crate::TestDb) keep using the configured user.
+///
+/// ```no_run
+/// use diesel_database_tester::{AppRole, TestDb};
+///
+/// // the privileges granted to `app` by the migrations, plus plain DML
+/// let tdb = TestDb::builder()
+///     .app_role(AppRole::read_write().member_of("app"))
+///     .build();
+/// let pool = tdb.pool(); // connects as the app role
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AppRole {
+    access: Access,
+    member_of: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+enum Access {
+    /// Nothing beyond connecting, privileges come from `member_of`.
+    #[default]
+    None,
+    ReadOnly,
+    ReadWrite,
+}
+
+impl AppRole {
+    /// A role that can connect and nothing else, for privileges granted
+    /// entirely through [`member_of`](Self::member_of).
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// `SELECT` on the tables and sequences of `public`.
+    pub fn read_only() -> Self {
+        Self {
+            access: Access::ReadOnly,
+            ..Self::default()
+        }
+    }
+
+    /// `SELECT`, `INSERT`, `UPDATE` and `DELETE` on the tables of `public`,
+    /// and the sequences behind their serial columns; no DDL, `TRUNCATE` or
+    /// ownership.
+    pub fn read_write() -> Self {
+        Self {
+            access: Access::ReadWrite,
+            ..Self::default()
+        }
+    }
+
+    /// Make the role a member of `group`, e.g. a `NOLOGIN` role the
+    /// migrations grant the application's privileges to.
+    pub fn member_of(mut self, group: impl Into<String>) -> Self {
+        self.member_of.push(group.into());
+        self
+    }
+
+    /// Statements creating the role `name` for the database `dbname`, run on
+    /// the database once it is migrated and seeded.
+    pub(crate) fn setup_sql(&self, name: &str, password: &str, dbname: &str) -> Vec<String> {
+        let role = quote_ident(name);
+        let mut statements = vec![
+            format!(
+                "CREATE ROLE {} LOGIN PASSWORD {}",
+                role,
+                quote_literal(password)
+            ),
+            format!("COMMENT ON ROLE {} IS {}", role, quote_literal(COMMENT)),
+            format!(
+                "GRANT CONNECT ON DATABASE {} TO {}",
+                quote_ident(dbname),
+                role
+            ),
+        ];
+        statements.extend(
+            self.member_of
+                .iter()
+                .map(|group| format!("GRANT {} TO {}", quote_ident(group), role)),
+        );
+        statements.extend(self.privileges_sql(name));
+        statements
+    }
+
+    /// Statements granting the role `name` its privileges inside the
+    /// database they run on, e.g. again after a restore.
+    pub(crate) fn privileges_sql(&self, name: &str) -> Vec<String> {
+        let role = quote_ident(name);
+        let (tables, sequences) = match self.access {
+            Access::None => return Vec::new(),
+            Access::ReadOnly => ("SELECT", "SELECT"),
+            Access::ReadWrite => ("SELECT, INSERT, UPDATE, DELETE", "USAGE, SELECT"),
+        };
+        vec![
+            format!("GRANT USAGE ON SCHEMA public TO {}", role),
+            format!(
+                "GRANT {} ON ALL TABLES IN SCHEMA public TO {}",
+                tables, role
+            ),
+            format!(
+                "GRANT {} ON ALL SEQUENCES IN SCHEMA public TO {}",
+                sequences, role
+            ),
+            // tables created later by the test itself, and their serial columns
+            format!(
+                "ALTER DEFAULT PRIVILEGES IN SCHEMA public GRANT {} ON TABLES TO {}",
+                tables, role
+            ),
+            format!(
+                "ALTER DEFAULT PRIVILEGES IN SCHEMA public GRANT {} ON SEQUENCES TO {}",
+                sequences, role
+            ),
+        ]
+    }
+}
+
+/// Statements taking back, inside a copy of its database, everything granted
+/// to the role `name`: the copy would otherwise keep the role from being
+/// dropped with its own database.
+pub(crate) fn revoke_sql(name: &str) -> Vec<String> {
+    let role = quote_ident(name);
+    vec![
+        format!("REVOKE ALL ON ALL TABLES IN SCHEMA public FROM {}", role),
+        format!("REVOKE ALL ON ALL SEQUENCES IN SCHEMA public FROM {}", role),
+        format!("REVOKE ALL ON SCHEMA public FROM {}", role),
+        format!(
+            "ALTER DEFAULT PRIVILEGES IN SCHEMA public REVOKE ALL ON TABLES FROM {}",
+            role
+        ),
+        format!(
+            "ALTER DEFAULT PRIVILEGES IN SCHEMA public REVOKE ALL ON SEQUENCES FROM {}",
+            role
+        ),
+    ]
+}
+
+/// Drop the role created for the dropped database `dbname`, if it had one:
+/// a role of that name tagged as created by this crate. Checked first, as
+/// only roles with `CREATEROLE` may even try to drop one.
+pub(crate) fn drop_if_created(conn: &mut PgConnection, dbname: &str) -> QueryResult<()> {
+    let exists: bool = select(
+        dsl::sql::<Bool>("EXISTS (SELECT 1 FROM pg_roles WHERE rolname = ")
+            .bind::<Text, _>(dbname)
+            .sql(" AND shobj_description(oid, 'pg_authid') = ")
+            .bind::<Text, _>(COMMENT)
+            .sql(")"),
+    )
+    .get_result(conn)?;
+    if exists {
+        diagnostics::execute(conn, &sql::drop_role(dbname))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roles_should_only_get_the_requested_privileges() {
+        let sql = AppRole::new()
+            .member_of("app")
+            .setup_sql("test_x", "pw", "test_x");
+        assert_eq!(
+            sql,
+            [
+                r#"CREATE ROLE "test_x" LOGIN PASSWORD 'pw'"#,
+                r#"COMMENT ON ROLE "test_x" IS 'diesel-database-tester app role'"#,
+                r#"GRANT CONNECT ON DATABASE "test_x" TO "test_x""#,
+                r#"GRANT "app" TO "test_x""#,
+            ]
+        );
+        let sql = AppRole::read_only().setup_sql("test_x", "pw", "test_x");
+        assert!(
+            sql.contains(&r#"GRANT SELECT ON ALL TABLES IN SCHEMA public TO "test_x""#.to_string())
+        );
+        assert!(!sql.iter().any(|statement| statement.contains("INSERT")));
+    }
+}
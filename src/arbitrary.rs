@@ -0,0 +1,219 @@
+//! quickcheck `Arbitrary` values constrained by the live schema.
+//!
+//! `Arbitrary::arbitrary` has no way to reach the database, so the columns of
+//! a table are registered once with [`TestDb::register_arbitrary`] and
+//! [`arbitrary_insertable!`](crate::arbitrary_insertable) implementations
+//! look them up when generating.
+
+use std::{
+    collections::HashMap,
+    error::Error,
+    sync::{Mutex, OnceLock},
+};
+
+use chrono::{DateTime, NaiveDate, NaiveDateTime};
+use quickcheck::{Arbitrary, Gen};
+use uuid::Uuid;
+
+use crate::{
+    introspect::{self, Column, MAX_EPOCH_SECS},
+    row::{Row, Value},
+    TestDb,
+};
+
+const ALPHABET: &[char] = &[
+    'a', 'b', 'c', 'd', 'e', 'f', 'g', 'h', 'i', 'j', 'k', 'l', 'm', 'n', 'o', 'p', 'q', 'r', 's',
+    't', 'u', 'v', 'w', 'x', 'y', 'z', 'A', 'B', 'C', 'D', 'E', 'F', 'G', 'H', 'I', 'J', 'K', 'L',
+    'M', 'N', 'O', 'P', 'Q', 'R', 'S', 'T', 'U', 'V', 'W', 'X', 'Y', 'Z', '0', '1', '2', '3', '4',
+    '5', '6', '7', '8', '9',
+];
+
+static TABLES: OnceLock<Mutex<HashMap<String, Vec<Column>>>> = OnceLock::new();
+
+fn tables() -> &'static Mutex<HashMap<String, Vec<Column>>> {
+    TABLES.get_or_init(Default::default)
+}
+
+impl TestDb {
+    /// Read the columns of `table` so [`arbitrary_row`] and the
+    /// [`arbitrary_insertable!`](crate::arbitrary_insertable) implementations
+    /// generate values its types, `NOT NULL`, varchar lengths and simple
+    /// check constraints accept.
+    pub fn register_arbitrary(&self, table: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let columns = introspect::columns(&mut self.connect(), table)?;
+        for column in columns.iter().filter(|column| !column.generated) {
+            column.check_supported()?;
+        }
+        tables().lock().unwrap().insert(table.to_string(), columns);
+        Ok(())
+    }
+}
+
+/// A row of a table registered with [`TestDb::register_arbitrary`].
+///
+/// # Panics
+///
+/// If `table` wasn't registered.
+pub fn arbitrary_row(table: &str, g: &mut Gen) -> Row {
+    let tables = tables().lock().unwrap();
+    let columns = tables.get(table).unwrap_or_else(|| {
+        panic!(
+            "Table {} isn't registered, call TestDb::register_arbitrary first",
+            table
+        )
+    });
+    Row {
+        table: table.to_string(),
+        values: columns
+            .iter()
+            .filter(|column| !column.generated)
+            .map(|column| (column.name.clone(), value(column, g)))
+            .collect(),
+    }
+}
+
+fn value(column: &Column, g: &mut Gen) -> Value {
+    if !column.not_null && u8::arbitrary(g) % 5 == 0 {
+        return Value::Null;
+    }
+    if !column.enum_labels.is_empty() {
+        return Value::Text(g.choose(&column.enum_labels).unwrap().clone());
+    }
+    let bounds = &column.bounds;
+    match column.type_name.as_str() {
+        "bool" => Value::Bool(bool::arbitrary(g)),
+        "int2" => {
+            let (lo, hi) = bounds.int_range(i16::MIN as i64, i16::MAX as i64);
+            Value::Int(uniform(g, lo, hi))
+        }
+        "int4" => {
+            let (lo, hi) = bounds.int_range(i32::MIN as i64, i32::MAX as i64);
+            Value::Int(uniform(g, lo, hi))
+        }
+        "int8" => {
+            let (lo, hi) = bounds.int_range(i64::MIN, i64::MAX);
+            Value::Int(uniform(g, lo, hi))
+        }
+        "float4" | "float8" => {
+            let (lo, hi) = bounds.float_range();
+            loop {
+                let f = lo + (hi - lo) * (u32::arbitrary(g) as f64 / u32::MAX as f64);
+                if bounds.contains(f) {
+                    return Value::Float(f);
+                }
+            }
+        }
+        "numeric" => {
+            let (_, scale) = column.numeric_precision();
+            let limit = column.numeric_limit();
+            let (lo, hi) = bounds.scaled(scale).int_range(-limit, limit);
+            Value::numeric(uniform(g, lo, hi), scale)
+        }
+        "text" | "varchar" | "bpchar" => {
+            let (min, max) = bounds.len_range();
+            let len = uniform(g, min as i64, max as i64) as usize;
+            Value::Text((0..len).map(|_| *g.choose(ALPHABET).unwrap()).collect())
+        }
+        "date" => Value::Date(
+            NaiveDate::default() + chrono::Duration::days(uniform(g, 0, MAX_EPOCH_SECS / 86_400)),
+        ),
+        "timestamp" | "timestamptz" => Value::Timestamp(
+            DateTime::from_timestamp(uniform(g, 0, MAX_EPOCH_SECS), 0)
+                .unwrap()
+                .naive_utc(),
+        ),
+        "uuid" => Value::Uuid(Uuid::from_u128(u128::arbitrary(g))),
+        other => unreachable!("unsupported type {}", other),
+    }
+}
+
+/// Uniformly pick an integer in `lo..=hi`.
+fn uniform(g: &mut Gen, lo: i64, hi: i64) -> i64 {
+    let span = (hi as i128 - lo as i128 + 1) as u128;
+    (lo as i128 + (u64::arbitrary(g) as u128 % span) as i128) as i64
+}
+
+/// Conversion of a generated [`Value`] into a field of an insertable struct.
+pub trait FromValue: Sized {
+    fn from_value(value: &Value) -> Self;
+}
+
+impl<T: FromValue> FromValue for Option<T> {
+    fn from_value(value: &Value) -> Self {
+        match value {
+            Value::Null => None,
+            value => Some(T::from_value(value)),
+        }
+    }
+}
+
+macro_rules! from_value {
+    ($($ty:ty => $($pattern:pat => $expr:expr),+;)+) => {
+        $(
+            impl FromValue for $ty {
+                fn from_value(value: &Value) -> Self {
+                    match value {
+                        $($pattern => $expr,)+
+                        other => panic!(
+                            "Can't convert {:?} into {}",
+                            other,
+                            stringify!($ty)
+                        ),
+                    }
+                }
+            }
+        )+
+    };
+}
+
+from_value! {
+    bool => Value::Bool(b) => *b;
+    i16 => Value::Int(i) => *i as i16;
+    i32 => Value::Int(i) => *i as i32;
+    i64 => Value::Int(i) => *i;
+    f32 => Value::Float(f) => *f as f32, Value::Numeric(n) => n.parse().unwrap();
+    f64 => Value::Float(f) => *f, Value::Numeric(n) => n.parse().unwrap();
+    String => Value::Text(s) => s.clone(), Value::Numeric(n) => n.clone();
+    NaiveDate => Value::Date(d) => *d;
+    NaiveDateTime => Value::Timestamp(ts) => *ts;
+    Uuid => Value::Uuid(u) => *u;
+}
+
+/// Implement quickcheck's `Arbitrary` for an insertable struct, filling each
+/// listed field from the column of the same name of a table registered with
+/// [`TestDb::register_arbitrary`]. Fields must implement [`FromValue`].
+///
+/// ```no_run
+/// use diesel_database_tester::{arbitrary_insertable, TestDb};
+///
+/// #[derive(Debug, Clone)]
+/// struct NewTodo {
+///     title: String,
+///     completed: bool,
+/// }
+/// arbitrary_insertable!(NewTodo, "todos", { title, completed });
+///
+/// let tdb = TestDb::new("localhost", 5432, "postgres", "postgres", "./migrations");
+/// tdb.register_arbitrary("todos").unwrap();
+/// fn title_fits(todo: NewTodo) -> bool {
+///     todo.title.len() <= 255
+/// }
+/// quickcheck::quickcheck(title_fits as fn(NewTodo) -> bool);
+/// ```
+#[macro_export]
+macro_rules! arbitrary_insertable {
+    ($ty:ty, $table:expr, { $($field:ident),* $(,)? }) => {
+        impl ::quickcheck::Arbitrary for $ty {
+            fn arbitrary(g: &mut ::quickcheck::Gen) -> Self {
+                let row = $crate::arbitrary_row($table, g);
+                Self {
+                    $($field: $crate::FromValue::from_value(
+                        row.get(stringify!($field)).unwrap_or_else(|| {
+                            panic!("Table {} has no column {}", $table, stringify!($field))
+                        }),
+                    ),)*
+                }
+            }
+        }
+    };
+}
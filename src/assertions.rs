@@ -0,0 +1,186 @@
+//! Assertions on the data side effects of test code.
+
+use diesel::{
+    dsl::sql,
+    select,
+    sql_types::{BigInt, Bool, Text},
+    PgConnection, QueryResult, RunQueryDsl,
+};
+
+use crate::{sql::quote_ident, TestDb};
+
+/// Number of rows currently in `table`.
+pub fn count_rows(conn: &mut PgConnection, table: &str) -> QueryResult<i64> {
+    select(sql::<BigInt>(&format!(
+        "(SELECT count(*) FROM {})",
+        quote_ident(table)
+    )))
+    .get_result(conn)
+}
+
+/// Run `f` and assert that it changed the number of rows in `table` by
+/// exactly `delta`, which may be negative.
+///
+/// ```no_run
+/// # use diesel_database_tester::{assert_row_delta, TestDb};
+/// # use diesel::RunQueryDsl;
+/// # let tdb = TestDb::builder().build();
+/// # let mut conn = tdb.pool().get().unwrap();
+/// assert_row_delta(&mut conn, "todos", 2, |conn| {
+///     diesel::sql_query("INSERT INTO todos (title) VALUES ('a'), ('b')")
+///         .execute(conn)
+///         .unwrap();
+/// });
+/// ```
+pub fn assert_row_delta<T>(
+    conn: &mut PgConnection,
+    table: &str,
+    delta: i64,
+    f: impl FnOnce(&mut PgConnection) -> T,
+) -> T {
+    let before = count_rows(conn, table).expect("Failed to count rows");
+    let result = f(conn);
+    let after = count_rows(conn, table).expect("Failed to count rows");
+    assert!(
+        after - before == delta,
+        "expected {} rows to change by {:+}, but it changed by {:+} ({} -> {})",
+        table,
+        delta,
+        after - before,
+        before,
+        after
+    );
+    result
+}
+
+/// Run `f` and assert that it added exactly `count` rows to `table`.
+pub fn assert_rows_added<T>(
+    conn: &mut PgConnection,
+    table: &str,
+    count: i64,
+    f: impl FnOnce(&mut PgConnection) -> T,
+) -> T {
+    assert_row_delta(conn, table, count, f)
+}
+
+/// Run `f` and assert that it removed exactly `count` rows from `table`.
+pub fn assert_rows_removed<T>(
+    conn: &mut PgConnection,
+    table: &str,
+    count: i64,
+    f: impl FnOnce(&mut PgConnection) -> T,
+) -> T {
+    assert_row_delta(conn, table, -count, f)
+}
+
+enum Conn<'a> {
+    Owned(PgConnection),
+    Borrowed(&'a mut PgConnection),
+}
+
+/// Checks of table contents that panic with the offending data, so a test
+/// can verify its side effects without writing a Diesel query for each.
+///
+/// ```no_run
+/// # use diesel_database_tester::TestDb;
+/// # let tdb = TestDb::builder().build();
+/// tdb.assertions()
+///     .assert_row_count("todos", 3)
+///     .assert_exists("todos", "title = 'x'");
+/// ```
+pub struct TestDbAssert<'a> {
+    conn: Conn<'a>,
+}
+
+impl<'a> TestDbAssert<'a> {
+    /// Assertions run on `conn`, e.g. to see the uncommitted writes of a test
+    /// transaction.
+    pub fn new(conn: &'a mut PgConnection) -> Self {
+        Self {
+            conn: Conn::Borrowed(conn),
+        }
+    }
+
+    fn conn(&mut self) -> &mut PgConnection {
+        match &mut self.conn {
+            Conn::Owned(conn) => conn,
+            Conn::Borrowed(conn) => conn,
+        }
+    }
+
+    /// Assert that `table` holds exactly `expected` rows.
+    pub fn assert_row_count(&mut self, table: &str, expected: i64) -> &mut Self {
+        let actual = count_rows(self.conn(), table)
+            .unwrap_or_else(|e| panic!("Failed to count rows of {}: {}", table, e));
+        assert!(
+            actual == expected,
+            "expected {} rows in {}, found {}",
+            expected,
+            table,
+            actual
+        );
+        self
+    }
+
+    /// Assert that some row of `table` matches the SQL `condition`.
+    pub fn assert_exists(&mut self, table: &str, condition: &str) -> &mut Self {
+        if !self.exists(table, condition) {
+            panic!(
+                "expected a row of {} where {}, found none in {}",
+                table,
+                condition,
+                self.dump_json(table)
+            );
+        }
+        self
+    }
+
+    /// Assert that no row of `table` matches the SQL `condition`.
+    pub fn assert_not_exists(&mut self, table: &str, condition: &str) -> &mut Self {
+        if self.exists(table, condition) {
+            panic!(
+                "expected no row of {} where {}, found some in {}",
+                table,
+                condition,
+                self.dump_json(table)
+            );
+        }
+        self
+    }
+
+    /// The rows of `table` as JSON objects keyed by column name, ordered by
+    /// the first column, usually the primary key.
+    pub fn dump_table(&mut self, table: &str) -> Vec<serde_json::Value> {
+        serde_json::from_str(&self.dump_json(table))
+            .unwrap_or_else(|e| panic!("Failed to parse the rows of {}: {}", table, e))
+    }
+
+    fn exists(&mut self, table: &str, condition: &str) -> bool {
+        select(sql::<Bool>(&format!(
+            "EXISTS (SELECT 1 FROM {} WHERE {})",
+            quote_ident(table),
+            condition
+        )))
+        .get_result(self.conn())
+        .unwrap_or_else(|e| panic!("Failed to query {} where {}: {}", table, condition, e))
+    }
+
+    fn dump_json(&mut self, table: &str) -> String {
+        select(sql::<Text>(&format!(
+            "(SELECT coalesce(json_agg(t), '[]')::text FROM (SELECT * FROM {} ORDER BY 1) t)",
+            quote_ident(table)
+        )))
+        .get_result(self.conn())
+        .unwrap_or_else(|e| panic!("Failed to dump {}: {}", table, e))
+    }
+}
+
+impl TestDb {
+    /// Assertions on the tables of this database, on a connection of their
+    /// own, see [`TestDbAssert`].
+    pub fn assertions(&self) -> TestDbAssert<'static> {
+        TestDbAssert {
+            conn: Conn::Owned(self.connect()),
+        }
+    }
+}
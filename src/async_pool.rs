@@ -0,0 +1,61 @@
+//! diesel-async connections to the test database, behind the `async` feature.
+//!
+//! The schema is still set up with sync diesel when the `TestDb` is built, so
+//! no async runtime is involved until the pool is used.
+
+use diesel_async::{
+    pooled_connection::{deadpool, AsyncDieselConnectionManager, ManagerConfig},
+    AsyncConnection, AsyncPgConnection, SimpleAsyncConnection,
+};
+
+use crate::{TestDb, TestDbError};
+
+pub type AsyncPool = deadpool::Pool<AsyncPgConnection>;
+
+impl TestDb {
+    /// A deadpool of [`AsyncPgConnection`]s to the test database, set up like
+    /// the connections of [`pool`](Self::pool) and sized by
+    /// [`TestDbBuilder::pool_size`](crate::TestDbBuilder::pool_size).
+    /// Credentials are resolved once, when the pool is created.
+    ///
+    /// ```no_run
+    /// # async fn example() {
+    /// use diesel_async::RunQueryDsl;
+    /// use diesel_database_tester::TestDb;
+    ///
+    /// let tdb = TestDb::builder().build();
+    /// let pool = tdb.async_pool();
+    /// let mut conn = pool.get().await.unwrap();
+    /// diesel::sql_query("SELECT 1").execute(&mut conn).await.unwrap();
+    /// # }
+    /// ```
+    pub fn async_pool(&self) -> AsyncPool {
+        self.try_async_pool().unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Like [`async_pool`](Self::async_pool), returning the error instead of
+    /// panicking.
+    pub fn try_async_pool(&self) -> Result<AsyncPool, TestDbError> {
+        let session_sql = self.config.session_sql.clone();
+        let mut config = ManagerConfig::default();
+        config.custom_setup = Box::new(move |url| {
+            let url = url.to_string();
+            let session_sql = session_sql.clone();
+            Box::pin(async move {
+                let mut conn = AsyncPgConnection::establish(&url).await?;
+                for sql in &session_sql {
+                    conn.batch_execute(sql)
+                        .await
+                        .map_err(diesel::ConnectionError::CouldntSetupConfiguration)?;
+                }
+                Ok(conn)
+            })
+        });
+        let manager = AsyncDieselConnectionManager::new_with_config(self.url(), config);
+        let mut builder = AsyncPool::builder(manager);
+        if let Some(size) = self.pool_size {
+            builder = builder.max_size(size as usize);
+        }
+        builder.build().map_err(TestDbError::AsyncPool)
+    }
+}
@@ -0,0 +1,221 @@
+//! `testdb`: create and drop test databases from shell scripts, Makefiles
+//! and other tooling outside Rust, and clean up after killed test runs.
+//!
+//! The server is taken from `--url`, then `$DATABASE_URL`, then the libpq
+//! environment variables, like [`TestDbBuilder::from_env`].
+
+use std::{env, error::Error, path::Path, process, time::Duration};
+
+use diesel_database_tester::{wait_for_server, TestDb, TestDbBuilder};
+
+const USAGE: &str = "\
+usage: testdb [--url URL] <command>
+
+commands:
+  create [--migrations DIR] [--prefix PREFIX] [--name LABEL]
+                          create a migrated database and print its url
+  drop [--force] <name>...
+                          drop test databases, refusing databases testdb
+                          didn't create unless --force is given
+  gc --older-than <AGE>   drop test databases created more than AGE ago,
+                          e.g. 90s, 30m, 1h or 2d
+  url [name]              print the url of the server, or of a database
+  wait [--timeout <AGE>]  wait until the server accepts connections,
+                          for up to AGE (default 30s)";
+
+type BoxError = Box<dyn Error + Send + Sync>;
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.iter().any(|arg| arg == "-h" || arg == "--help") {
+        println!("{}", USAGE);
+        return;
+    }
+    if let Err(e) = run(args) {
+        eprintln!("testdb: {}", e);
+        process::exit(1);
+    }
+}
+
+fn run(mut args: Vec<String>) -> Result<(), BoxError> {
+    let url = take_option(&mut args, "--url")?;
+    let builder = match &url {
+        Some(url) => TestDbBuilder::from_url(url),
+        None => TestDbBuilder::from_env(),
+    };
+    if args.is_empty() {
+        return Err(format!("missing command\n{}", USAGE).into());
+    }
+    let command = args.remove(0);
+    match command.as_str() {
+        "create" => create(builder, args),
+        "drop" => {
+            let force = take_flag(&mut args, "--force");
+            if args.is_empty() {
+                return Err("drop needs the name of a database".into());
+            }
+            let server_url = builder.server_url()?;
+            if !force {
+                for dbname in &args {
+                    if !TestDb::is_test_database(&server_url, dbname)? {
+                        return Err(format!(
+                            "{} wasn't created by testdb, pass --force to drop it anyway",
+                            dbname
+                        )
+                        .into());
+                    }
+                }
+            }
+            for dbname in &args {
+                TestDb::drop_database(&server_url, dbname)?;
+                eprintln!("dropped {}", dbname);
+            }
+            Ok(())
+        }
+        "gc" => {
+            let older_than =
+                take_option(&mut args, "--older-than")?.ok_or("gc needs --older-than")?;
+            no_more(&args)?;
+            let older_than = parse_age(&older_than)?;
+            for dbname in TestDb::cleanup_stale(&builder.server_url()?, older_than)? {
+                println!("{}", dbname);
+            }
+            Ok(())
+        }
+        "url" => {
+            let url = match args.as_slice() {
+                [] => builder.server_url()?,
+                [dbname] => builder.database_url(dbname)?,
+                _ => return Err(format!("unexpected arguments {:?}", &args[1..]).into()),
+            };
+            println!("{}", url);
+            Ok(())
+        }
+        "wait" => {
+            let timeout = take_option(&mut args, "--timeout")?;
+            no_more(&args)?;
+            let timeout = match timeout {
+                Some(timeout) => parse_age(&timeout)?,
+                None => Duration::from_secs(30),
+            };
+            wait_for_server(&builder.server_url()?, timeout)?;
+            Ok(())
+        }
+        _ => Err(format!("unknown command {}\n{}", command, USAGE).into()),
+    }
+}
+
+fn create(mut builder: TestDbBuilder, mut args: Vec<String>) -> Result<(), BoxError> {
+    // like diesel, pick up the migrations of the project the tool runs in
+    let migrations = take_option(&mut args, "--migrations")?.or_else(|| {
+        Path::new("migrations")
+            .is_dir()
+            .then(|| "migrations".into())
+    });
+    if let Some(dir) = migrations {
+        builder = builder.migrations_dir(dir);
+    }
+    if let Some(prefix) = take_option(&mut args, "--prefix")? {
+        builder = builder.prefix(prefix);
+    }
+    if let Some(label) = take_option(&mut args, "--name")? {
+        builder = builder.named(label);
+    }
+    no_more(&args)?;
+    // the database outlives this process, until `testdb drop` or `testdb gc`
+    println!("{}", builder.try_build()?.leak());
+    Ok(())
+}
+
+/// Remove `--name value` or `--name=value` from `args`, returning the value.
+fn take_option(args: &mut Vec<String>, name: &str) -> Result<Option<String>, BoxError> {
+    let Some(i) = args
+        .iter()
+        .position(|arg| arg == name || arg.starts_with(&format!("{}=", name)))
+    else {
+        return Ok(None);
+    };
+    let arg = args.remove(i);
+    match arg.split_once('=') {
+        Some((_, value)) => Ok(Some(value.to_string())),
+        None if i < args.len() => Ok(Some(args.remove(i))),
+        None => Err(format!("{} needs a value", name).into()),
+    }
+}
+
+/// Remove the flag `name` from `args`, returning whether it was there.
+fn take_flag(args: &mut Vec<String>, name: &str) -> bool {
+    let len = args.len();
+    args.retain(|arg| arg != name);
+    args.len() != len
+}
+
+fn no_more(args: &[String]) -> Result<(), BoxError> {
+    match args.first() {
+        Some(arg) => Err(format!("unexpected argument {}", arg).into()),
+        None => Ok(()),
+    }
+}
+
+/// A duration like `90s`, `30m`, `1h` or `2d`.
+fn parse_age(age: &str) -> Result<Duration, BoxError> {
+    let invalid = || format!("invalid age {}, expected e.g. 30m, 1h or 2d", age);
+    let unit_at = age
+        .find(|c: char| !c.is_ascii_digit())
+        .ok_or_else(invalid)?;
+    let (count, unit) = age.split_at(unit_at);
+    let count: u64 = count.parse().map_err(|_| invalid())?;
+    let seconds = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        _ => return Err(invalid().into()),
+    };
+    Ok(Duration::from_secs(count * seconds))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ages_should_be_parsed_with_their_unit() {
+        assert_eq!(parse_age("90s").unwrap(), Duration::from_secs(90));
+        assert_eq!(parse_age("30m").unwrap(), Duration::from_secs(30 * 60));
+        assert_eq!(parse_age("1h").unwrap(), Duration::from_secs(60 * 60));
+        assert_eq!(
+            parse_age("2d").unwrap(),
+            Duration::from_secs(2 * 24 * 60 * 60)
+        );
+        assert!(parse_age("1").is_err());
+        assert!(parse_age("h").is_err());
+        assert!(parse_age("1w").is_err());
+    }
+
+    #[test]
+    fn options_should_be_taken_in_both_forms() {
+        let mut args: Vec<String> = ["--url", "postgres://a", "create", "--prefix=it"]
+            .map(String::from)
+            .into();
+        assert_eq!(
+            take_option(&mut args, "--url").unwrap().as_deref(),
+            Some("postgres://a")
+        );
+        assert_eq!(
+            take_option(&mut args, "--prefix").unwrap().as_deref(),
+            Some("it")
+        );
+        assert_eq!(take_option(&mut args, "--name").unwrap(), None);
+        assert_eq!(args, ["create"]);
+        assert!(take_option(&mut vec!["--url".into()], "--url").is_err());
+    }
+
+    #[test]
+    fn flags_should_be_taken_anywhere() {
+        let mut args: Vec<String> = ["test_a", "--force", "test_b"].map(String::from).into();
+        assert!(take_flag(&mut args, "--force"));
+        assert!(!take_flag(&mut args, "--force"));
+        assert_eq!(args, ["test_a", "test_b"]);
+    }
+}
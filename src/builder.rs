@@ -0,0 +1,1205 @@
+use std::{
+    env,
+    error::Error,
+    fmt,
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use diesel::{migration::MigrationSource, pg::Pg, PgConnection};
+
+use crate::{
+    connection::{self, ConnectionConfig, Transport},
+    credentials::{CredentialProvider, Credentials, RefreshingCredentials},
+    diagnostics,
+    fixtures::Seed,
+    hooks::MigrationHook,
+    migrations::MigrationSet,
+    naming::{self, DbNaming, NameFn, NameParts, Namer},
+    pgpass,
+    plan::DryRun,
+    progress::OnMigration,
+    schema_cache,
+    service::{self, ServiceParams},
+    sql::{self, DatabaseOptions},
+    AppRole, ConnectRetry, MigrationProgress, Profile, Quota, TempSchemaDb, TestDb, TestDbError,
+};
+
+/// Configures and creates a [`TestDb`].
+///
+/// Connection settings that are not set explicitly fall back to the selected
+/// [`service`](Self::service) definition, then to the standard libpq
+/// environment variables (`PGHOST`, `PGPORT`, `PGUSER`, `PGPASSWORD`,
+/// `PGSSLMODE`), then to `localhost:5432` as `postgres`.
+///
+/// ```no_run
+/// use diesel_database_tester::{DbNaming, TestDb};
+///
+/// let tdb = TestDb::builder()
+///     .host("localhost")
+///     .port(5432)
+///     .user("postgres")
+///     .password("postgres")
+///     .naming(DbNaming::Short)
+///     .build();
+/// ```
+#[derive(Clone, Default)]
+pub struct TestDbBuilder {
+    pub(crate) host: Option<String>,
+    pub(crate) port: Option<u16>,
+    pub(crate) user: Option<String>,
+    pub(crate) password: Option<String>,
+    pub(crate) sslmode: Option<String>,
+    pub(crate) params: Vec<(String, String)>,
+    pub(crate) service: Option<String>,
+    pub(crate) credentials: Option<Arc<dyn CredentialProvider>>,
+    pub(crate) refresh: Option<Arc<dyn CredentialProvider>>,
+    pub(crate) transport: Option<Arc<dyn Transport>>,
+    pub(crate) naming: DbNaming,
+    pub(crate) name_with: Option<NameFn>,
+    pub(crate) label: Option<String>,
+    pub(crate) analyze_after_seed: bool,
+    /// `ALTER DATABASE ... SET` defaults applied once set up.
+    pub(crate) database_settings: Vec<(String, String)>,
+    pub(crate) random_seed: Option<f64>,
+    pub(crate) schema_cache: Option<PathBuf>,
+    pub(crate) shared_template: bool,
+    pub(crate) on_migration: Option<OnMigration>,
+    pub(crate) extensions: Vec<String>,
+    /// `CREATE TYPE`/`CREATE DOMAIN` statements run before the migrations.
+    pub(crate) types: Vec<String>,
+    pub(crate) database_options: DatabaseOptions,
+    pub(crate) minimum_version: Option<u32>,
+    pub(crate) connect_retry: Option<ConnectRetry>,
+    pub(crate) app_role: Option<AppRole>,
+    pub(crate) quota: Option<Quota>,
+    pub(crate) profile: Option<String>,
+    pub(crate) migrations: MigrationSet,
+    pub(crate) prefix: Option<String>,
+    pub(crate) pool_size: Option<u32>,
+    pub(crate) pool_timeout: Option<Duration>,
+    pub(crate) pool_min_idle: Option<u32>,
+    pub(crate) pool_idle_timeout: Option<Duration>,
+    pub(crate) fixtures: Vec<PathBuf>,
+    /// Statements run on every connection handed to the test.
+    pub(crate) session_sql: Vec<String>,
+    /// Log the statements of the test database's connections.
+    pub(crate) trace_sql: bool,
+    pub(crate) drop_timeout: Option<Duration>,
+    pub(crate) seeds: Vec<Seed>,
+    pub(crate) before_migrations: Vec<Arc<dyn MigrationHook>>,
+    pub(crate) after_migrations: Vec<Arc<dyn MigrationHook>>,
+    pub(crate) container: bool,
+    /// The `postgres` image tag of the container, the module's default when
+    /// unset.
+    pub(crate) container_tag: Option<String>,
+    pub(crate) embedded: bool,
+    /// Also create a read-only copy of the database.
+    pub(crate) replica: bool,
+}
+
+impl fmt::Debug for TestDbBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TestDbBuilder")
+            .field("host", &self.host)
+            .field("port", &self.port)
+            .field("user", &self.user)
+            .field("password", &self.password.as_ref().map(|_| "********"))
+            .field("sslmode", &self.sslmode)
+            .field("params", &self.params)
+            .field("service", &self.service)
+            .field("credentials", &self.credentials.as_ref().map(|_| ".."))
+            .field("refresh", &self.refresh.as_ref().map(|_| ".."))
+            .field("transport", &self.transport.as_ref().map(|_| ".."))
+            .field("naming", &self.naming)
+            .field("name_with", &self.name_with.as_ref().map(|_| ".."))
+            .field("label", &self.label)
+            .field("analyze_after_seed", &self.analyze_after_seed)
+            .field("database_settings", &self.database_settings)
+            .field("random_seed", &self.random_seed)
+            .field("schema_cache", &self.schema_cache)
+            .field("shared_template", &self.shared_template)
+            .field("on_migration", &self.on_migration.as_ref().map(|_| ".."))
+            .field("extensions", &self.extensions)
+            .field("types", &self.types)
+            .field("database_options", &self.database_options)
+            .field("minimum_version", &self.minimum_version)
+            .field("connect_retry", &self.connect_retry)
+            .field("app_role", &self.app_role)
+            .field("quota", &self.quota)
+            .field("profile", &self.profile)
+            .field("migrations", &self.migrations.names())
+            .field("prefix", &self.prefix)
+            .field("pool_size", &self.pool_size)
+            .field("pool_timeout", &self.pool_timeout)
+            .field("pool_min_idle", &self.pool_min_idle)
+            .field("pool_idle_timeout", &self.pool_idle_timeout)
+            .field("fixtures", &self.fixtures)
+            .field("session_sql", &self.session_sql)
+            .field("seeds", &self.seeds.len())
+            .field("before_migrations", &self.before_migrations.len())
+            .field("after_migrations", &self.after_migrations.len())
+            .field("container", &self.container)
+            .field("container_tag", &self.container_tag)
+            .field("embedded", &self.embedded)
+            .field("replica", &self.replica)
+            .finish()
+    }
+}
+
+impl TestDbBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Take host, port, credentials and query parameters (`sslmode`,
+    /// `options`, ...) from `url`. Its database name is ignored, test
+    /// databases replace it.
+    pub fn from_url(url: &str) -> Self {
+        let parsed = connection::parse_url(url).unwrap_or_else(|e| panic!("{}", e));
+        let mut builder = Self {
+            host: parsed.host,
+            port: parsed.port,
+            user: parsed.user,
+            password: parsed.password,
+            ..Self::default()
+        };
+        for (key, value) in parsed.params {
+            if key == "sslmode" {
+                builder.sslmode = Some(value);
+            } else if key == "host" {
+                // `postgres:///db?host=/var/run/postgresql`
+                builder.host = Some(value);
+            } else if let ("port", Ok(port)) = (key.as_str(), value.parse()) {
+                builder.port = Some(port);
+            } else {
+                builder.params.push((key, value));
+            }
+        }
+        builder
+    }
+
+    /// Take the server from `$DATABASE_URL` like [`from_url`](Self::from_url)
+    /// when it is set, so CI only has to configure that one variable.
+    /// Otherwise the libpq environment variables apply as usual.
+    pub fn from_env() -> Self {
+        Self::from_database_url(env::var("DATABASE_URL").ok())
+    }
+
+    /// [`from_env`](Self::from_env) with the value of `$DATABASE_URL` given.
+    pub(crate) fn from_database_url(url: Option<String>) -> Self {
+        match url {
+            Some(url) if !url.is_empty() => Self::from_url(&url),
+            _ => Self::new(),
+        }
+    }
+
+    pub fn host(mut self, host: impl Into<String>) -> Self {
+        self.host = Some(host.into());
+        self
+    }
+
+    /// Connect through the unix socket in `dir`, e.g. `/var/run/postgresql`,
+    /// instead of TCP. The [port](Self::port) still picks the socket file,
+    /// `.s.PGSQL.5432` by default.
+    pub fn socket_dir(self, dir: impl AsRef<Path>) -> Self {
+        self.host(dir.as_ref().to_string_lossy())
+    }
+
+    pub fn port(mut self, port: u16) -> Self {
+        self.port = Some(port);
+        self
+    }
+
+    pub fn user(mut self, user: impl Into<String>) -> Self {
+        self.user = Some(user.into());
+        self
+    }
+
+    /// Password for `user`. When left empty it is looked up in the libpq
+    /// password file (`$PGPASSFILE` or `~/.pgpass`).
+    pub fn password(mut self, password: impl Into<String>) -> Self {
+        self.password = Some(password.into());
+        self
+    }
+
+    /// libpq `sslmode` (`disable`, `require`, `verify-full`, ...) for every
+    /// connection made to the server.
+    pub fn sslmode(mut self, sslmode: impl Into<String>) -> Self {
+        self.sslmode = Some(sslmode.into());
+        self
+    }
+
+    /// Add the libpq connection parameter `key` (e.g. `sslrootcert`,
+    /// `target_session_attrs`) to every connection url, replacing an earlier
+    /// value. Keys and values are percent-encoded.
+    ///
+    /// ```no_run
+    /// use diesel_database_tester::TestDb;
+    ///
+    /// let tdb = TestDb::builder()
+    ///     .sslmode("verify-full")
+    ///     .param("sslrootcert", "/etc/ssl/staging-ca.pem")
+    ///     .build();
+    /// ```
+    pub fn param(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        let key = key.into();
+        if key == "sslmode" {
+            return self.sslmode(value);
+        }
+        self.params.retain(|(existing, _)| *existing != key);
+        self.params.push((key, value.into()));
+        self
+    }
+
+    /// Report `name` as the `application_name` of every connection, shown in
+    /// `pg_stat_activity` and the server log.
+    pub fn application_name(self, name: impl Into<String>) -> Self {
+        self.param("application_name", name)
+    }
+
+    /// Fetch the user name and password from `provider` when building, unless
+    /// they are set explicitly.
+    pub fn credentials(mut self, provider: impl CredentialProvider + 'static) -> Self {
+        self.credentials = Some(Arc::new(provider));
+        self
+    }
+
+    /// Register a callback that replaces the credentials used for new
+    /// connections once the current ones are about to expire, or when the
+    /// server rejects them. Defaults to the [`credentials`](Self::credentials)
+    /// provider, so multi-hour runs keep working after the first token expires.
+    pub fn refresh_credentials(mut self, refresh: impl CredentialProvider + 'static) -> Self {
+        self.refresh = Some(Arc::new(refresh));
+        self
+    }
+
+    /// Route every new connection through `transport` (e.g. a Cloud SQL Auth
+    /// Proxy sidecar) instead of connecting directly to host and port.
+    pub fn transport(mut self, transport: impl Transport + 'static) -> Self {
+        self.transport = Some(Arc::new(transport));
+        self
+    }
+
+    /// Resolve connection parameters from a named service in the libpq
+    /// service file (`~/.pg_service.conf` or `$PGSYSCONFDIR/pg_service.conf`).
+    /// Explicitly set parameters still take precedence. Defaults to `$PGSERVICE`.
+    pub fn service(mut self, service: impl Into<String>) -> Self {
+        self.service = Some(service.into());
+        self
+    }
+
+    /// Apply `migrations` instead of the ones embedded in this crate, usually
+    /// the `MIGRATIONS` of the project under test:
+    ///
+    /// ```no_run
+    /// use diesel_database_tester::TestDb;
+    /// use diesel_migrations::{embed_migrations, EmbeddedMigrations};
+    ///
+    /// pub const MIGRATIONS: EmbeddedMigrations = embed_migrations!();
+    ///
+    /// let tdb = TestDb::builder().migrations(MIGRATIONS).build();
+    /// ```
+    pub fn migrations(
+        mut self,
+        migrations: impl MigrationSource<Pg> + Send + Sync + 'static,
+    ) -> Self {
+        self.migrations = MigrationSet::new(migrations);
+        self
+    }
+
+    /// Apply the migrations found in the diesel migrations directory `dir`,
+    /// read when the database is built.
+    ///
+    /// # Panics
+    ///
+    /// If `dir` isn't a migrations directory.
+    pub fn migrations_dir(mut self, dir: impl AsRef<Path>) -> Self {
+        self.migrations =
+            MigrationSet::try_from_dir(dir.as_ref()).unwrap_or_else(|e| panic!("{}", e));
+        self
+    }
+
+    /// Apply `migrations` after the ones given so far, e.g. a service's own
+    /// migrations after those of a shared crate. Each source's pending
+    /// migrations run in version order, one source after the other, all
+    /// recorded in the same `__diesel_schema_migrations` table. Without an
+    /// earlier [`migrations`](Self::migrations), this replaces the ones
+    /// embedded in this crate.
+    ///
+    /// ```no_run
+    /// use diesel_database_tester::TestDb;
+    /// # use diesel_database_tester::MIGRATIONS as CORE_MIGRATIONS;
+    ///
+    /// let tdb = TestDb::builder()
+    ///     .migrations(CORE_MIGRATIONS)
+    ///     .add_migrations_dir("./migrations")
+    ///     .build();
+    /// ```
+    pub fn add_migrations(
+        mut self,
+        migrations: impl MigrationSource<Pg> + Send + Sync + 'static,
+    ) -> Self {
+        self.migrations = self.migrations.then(MigrationSet::new(migrations));
+        self
+    }
+
+    /// Like [`add_migrations`](Self::add_migrations), for the migrations in
+    /// the diesel migrations directory `dir`.
+    ///
+    /// # Panics
+    ///
+    /// If `dir` isn't a migrations directory.
+    pub fn add_migrations_dir(mut self, dir: impl AsRef<Path>) -> Self {
+        let added = MigrationSet::try_from_dir(dir.as_ref()).unwrap_or_else(|e| panic!("{}", e));
+        self.migrations = self.migrations.then(added);
+        self
+    }
+
+    /// Start database names with `prefix` instead of `test_`, e.g. to tell
+    /// apart the databases of several projects sharing a server. Without it,
+    /// `$TESTDB_PREFIX` applies, e.g. `ci_1234_` to trace leaked databases
+    /// back to the CI job that created them.
+    ///
+    /// # Panics
+    ///
+    /// If `prefix` is empty or contains anything but ASCII letters, digits
+    /// and underscores.
+    pub fn prefix(mut self, prefix: impl Into<String>) -> Self {
+        let prefix = prefix.into();
+        assert!(
+            naming::is_valid_prefix(&prefix),
+            "database name prefix must only contain ASCII letters, digits and underscores"
+        );
+        self.prefix = Some(prefix);
+        self
+    }
+
+    /// Create the database on a disposable Postgres container started on
+    /// demand and shared with the other `TestDb`s of the process, instead of
+    /// an existing server. The container is stopped once the last of them is
+    /// dropped. Needs a docker daemon; the connection settings are replaced
+    /// with the container's.
+    #[cfg(feature = "testcontainers")]
+    pub fn container(mut self) -> Self {
+        self.container = true;
+        self
+    }
+
+    /// Create the database on a throwaway server run from the local Postgres
+    /// binaries (`initdb`, `pg_ctl`) in a temporary directory, started on
+    /// demand and shared with the other `TestDb`s of the process, instead of
+    /// an existing server. The server is stopped and its directory deleted
+    /// once the last of them is dropped. The binaries come from
+    /// `$TESTDB_PG_BIN_DIR` or the `PATH`, and refuse to run as root; the
+    /// connection settings are replaced with the server's.
+    #[cfg(feature = "embedded-pg")]
+    pub fn embedded(mut self) -> Self {
+        self.embedded = true;
+        self
+    }
+
+    /// Most connections [`TestDb::pool`] opens, 10 by default.
+    pub fn pool_size(mut self, size: u32) -> Self {
+        self.pool_size = Some(size);
+        self
+    }
+
+    /// How long checking a connection out of [`TestDb::pool`] may wait, 30
+    /// seconds by default.
+    pub fn pool_timeout(mut self, timeout: Duration) -> Self {
+        self.pool_timeout = Some(timeout);
+        self
+    }
+
+    /// Fewest idle connections [`TestDb::pool`] keeps open, by default as many
+    /// as its size. Lower it so parallel tests don't exhaust `max_connections`
+    /// with connections they never use.
+    pub fn pool_min_idle(mut self, min_idle: u32) -> Self {
+        self.pool_min_idle = Some(min_idle);
+        self
+    }
+
+    /// Close connections of [`TestDb::pool`] idle for longer than `timeout`
+    /// (beyond the minimum idle ones), 10 minutes by default.
+    pub fn pool_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.pool_idle_timeout = Some(timeout);
+        self
+    }
+
+    /// Give up connecting to the server after `timeout`, rounded up to whole
+    /// seconds (libpq `connect_timeout`).
+    pub fn connect_timeout(self, timeout: Duration) -> Self {
+        let secs = timeout.as_secs() + u64::from(timeout.subsec_nanos() > 0);
+        self.param("connect_timeout", secs.max(1).to_string())
+    }
+
+    /// Log every statement run on the test database's connections, pooled or
+    /// not, as a `tracing` debug event with its duration. Setup itself is
+    /// covered by spans, see the `tracing` feature.
+    #[cfg(feature = "tracing")]
+    pub fn trace_sql(mut self) -> Self {
+        self.trace_sql = true;
+        self
+    }
+
+    /// Give up dropping the database after `timeout`, 30 seconds by default,
+    /// so teardown can't hang a test run on an unresponsive server. A database
+    /// that couldn't be dropped is logged and listed by
+    /// [`leaked_databases`](crate::leaked_databases).
+    pub fn drop_timeout(mut self, timeout: Duration) -> Self {
+        self.drop_timeout = Some(timeout);
+        self
+    }
+
+    /// Also create a "replica" of the database once it is set up, a copy
+    /// whose [`replica_pool`](TestDb::replica_pool) connections are read-only,
+    /// to exercise the read side of read/write splitting. It doesn't follow
+    /// later writes to the primary until
+    /// [`sync_replica`](TestDb::sync_replica).
+    pub fn replica(mut self) -> Self {
+        self.replica = true;
+        self
+    }
+
+    /// Keep retrying the connections of setup while the server refuses them,
+    /// e.g. because it is still starting, instead of failing on the first.
+    /// Other failures, like wrong credentials, are never retried.
+    pub fn connect_retry(mut self, retry: ConnectRetry) -> Self {
+        self.connect_retry = Some(retry);
+        self
+    }
+
+    /// Choose how the unique part of the database name is generated.
+    pub fn naming(mut self, naming: DbNaming) -> Self {
+        self.naming = naming;
+        self
+    }
+
+    /// Build database names with `name_with` instead of
+    /// `{prefix}{unique}_{label}`. The name must stay unique, so keep
+    /// [`NameParts::unique`] in it, and fit the 63 bytes of a Postgres
+    /// identifier, or building the database fails.
+    ///
+    /// ```no_run
+    /// use diesel_database_tester::TestDb;
+    ///
+    /// let job = std::env::var("CI_JOB_ID").unwrap_or_default();
+    /// let tdb = TestDb::builder()
+    ///     .name_with(move |parts| format!("ci_{}_orders_{}", job, parts.unique))
+    ///     .build();
+    /// ```
+    pub fn name_with(
+        mut self,
+        name_with: impl Fn(&NameParts) -> String + Send + Sync + 'static,
+    ) -> Self {
+        self.name_with = Some(Arc::new(name_with));
+        self
+    }
+
+    /// Embed a sanitized test path (e.g. `users::create_flow`) in the database
+    /// name, so leftover databases and `pg_stat_activity` rows can be traced
+    /// back to the test that created them.
+    pub fn named(mut self, label: impl Into<String>) -> Self {
+        self.label = Some(label.into());
+        self
+    }
+
+    /// Run `sql` on every new connection to the test database, pooled or not,
+    /// before the test gets it; setup itself isn't affected. Can be called
+    /// repeatedly, statements run in the order they were added. For more than
+    /// SQL, add a connection customizer with [`TestDb::pool_with`].
+    ///
+    /// ```no_run
+    /// use diesel_database_tester::TestDb;
+    ///
+    /// let tdb = TestDb::builder()
+    ///     .session_sql("SET search_path TO app, public")
+    ///     .build();
+    /// ```
+    pub fn session_sql(mut self, sql: impl Into<String>) -> Self {
+        self.session_sql.push(sql.into());
+        self
+    }
+
+    /// Set the setting `name` to `value` on every new connection to the test
+    /// database, e.g. `("timezone", "UTC")`, see [`session_sql`](Self::session_sql).
+    pub fn session_setting(self, name: &str, value: &str) -> Self {
+        self.session_sql(sql::set(name, value))
+    }
+
+    /// Run `SELECT setseed(seed)` on every connection to the test database, so
+    /// `random()` and `ORDER BY random()` return the same sequence on every run.
+    /// `seed` must be between -1 and 1.
+    ///
+    /// Only `random()` is affected: column defaults using other volatile
+    /// functions (`gen_random_uuid()`, `now()`, `clock_timestamp()`) still
+    /// differ between runs and are best set explicitly by the test.
+    pub fn random_seed(mut self, seed: f64) -> Self {
+        assert!(
+            (-1.0..=1.0).contains(&seed),
+            "random seed must be between -1 and 1"
+        );
+        self.random_seed = Some(seed);
+        self
+    }
+
+    /// Cache a dump of the migrated database, rows inserted by migrations
+    /// included, in `dir` and restore it instead of running the migrations,
+    /// for as long as the migrations don't change.
+    /// Needs `pg_dump` on the `PATH` to fill the cache.
+    pub fn schema_cache(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.schema_cache = Some(dir.into());
+        self
+    }
+
+    /// Clone the database from a long-lived `tpl_<project>_<hash>` template
+    /// shared by every process and CI job using the server, built once per
+    /// set of migrations. When a new one is built, the project's templates
+    /// unused for a day are dropped.
+    pub fn shared_template(mut self, shared: bool) -> Self {
+        self.shared_template = shared;
+        self
+    }
+
+    /// Call `callback` after each migration run during setup, e.g. to print
+    /// progress for long first-time setups that would otherwise look hung.
+    ///
+    /// ```no_run
+    /// use diesel_database_tester::TestDb;
+    ///
+    /// let tdb = TestDb::builder()
+    ///     .on_migration(|p| eprintln!("[{}/{}] {} ({:?})", p.index, p.total, p.name, p.duration))
+    ///     .build();
+    /// ```
+    pub fn on_migration(
+        mut self,
+        callback: impl Fn(&MigrationProgress) + Send + Sync + 'static,
+    ) -> Self {
+        self.on_migration = Some(Arc::new(callback));
+        self
+    }
+
+    /// Run `CREATE EXTENSION IF NOT EXISTS` for each of `extensions` before
+    /// the migrations, for migrations assuming they are installed. The
+    /// extensions must be available on the server, and the user allowed to
+    /// create them.
+    ///
+    /// ```no_run
+    /// use diesel_database_tester::TestDb;
+    ///
+    /// let tdb = TestDb::builder()
+    ///     .with_extensions(&["uuid-ossp", "pg_trgm"])
+    ///     .build();
+    /// ```
+    pub fn with_extensions(mut self, extensions: &[&str]) -> Self {
+        self.extensions
+            .extend(extensions.iter().map(|name| name.to_string()));
+        self
+    }
+
+    /// Create the enum type `name` before running the migrations, for
+    /// migrations or diesel enum mappings expecting it to exist already.
+    pub fn create_enum(mut self, name: &str, labels: &[&str]) -> Self {
+        self.types.push(sql::create_enum(name, labels));
+        self
+    }
+
+    /// Create the domain `name` over `base_type` before running the
+    /// migrations, optionally constrained by `check`, e.g. `VALUE > 0`.
+    pub fn create_domain(mut self, name: &str, base_type: &str, check: Option<&str>) -> Self {
+        self.types.push(sql::create_domain(name, base_type, check));
+        self
+    }
+
+    /// Create the composite type `name` with `(field, sql type)` pairs before
+    /// running the migrations.
+    pub fn create_composite(mut self, name: &str, fields: &[(&str, &str)]) -> Self {
+        self.types.push(sql::create_composite(name, fields));
+        self
+    }
+
+    /// Create the database with `collation` as its `LC_COLLATE` and
+    /// `LC_CTYPE`, e.g. `de_DE.utf8`. The locale must exist on the server.
+    /// Such databases are cloned from `template0`, so
+    /// [`shared_template`](Self::shared_template) is ignored.
+    pub fn collation(self, collation: impl Into<String>) -> Self {
+        let collation = collation.into();
+        self.lc_collate(collation.clone()).lc_ctype(collation)
+    }
+
+    /// Create the database with `LC_COLLATE` set to `locale`, see
+    /// [`collation`](Self::collation).
+    pub fn lc_collate(mut self, locale: impl Into<String>) -> Self {
+        self.database_options.lc_collate = Some(locale.into());
+        self
+    }
+
+    /// Create the database with `LC_CTYPE` set to `locale`, see
+    /// [`collation`](Self::collation).
+    pub fn lc_ctype(mut self, locale: impl Into<String>) -> Self {
+        self.database_options.lc_ctype = Some(locale.into());
+        self
+    }
+
+    /// Create the database with `encoding`, e.g. `LATIN1`, cloned from
+    /// `template0` like a [`collation`](Self::collation). The encoding must
+    /// suit the locale.
+    pub fn encoding(mut self, encoding: impl Into<String>) -> Self {
+        self.database_options.encoding = Some(encoding.into());
+        self
+    }
+
+    /// Make `role` the owner of the database. The connecting user must be a
+    /// member of it.
+    pub fn owner(mut self, role: impl Into<String>) -> Self {
+        self.database_options.owner = Some(role.into());
+        self
+    }
+
+    /// Clone the database from `template0` instead of `template1`, so nothing
+    /// installed into `template1` on the server leaks into the tests.
+    /// [`shared_template`](Self::shared_template) is ignored.
+    pub fn template0(mut self) -> Self {
+        self.database_options.template0 = true;
+        self
+    }
+
+    /// Refuse to set up on servers older than Postgres `major`, e.g. `14`,
+    /// instead of failing later with a confusing syntax error. Individual
+    /// tests can skip themselves with [`require_version!`](crate::require_version).
+    pub fn minimum_version(mut self, major: u32) -> Self {
+        self.minimum_version = Some(major);
+        self
+    }
+
+    /// Run `f` against a fresh database for each of `collations`, to catch
+    /// sorting and uniqueness behavior that differs across locales. Panics
+    /// from `f` are reported with the collation they happened under.
+    ///
+    /// ```no_run
+    /// use diesel_database_tester::TestDb;
+    ///
+    /// TestDb::builder().run_with_collations(&["C", "en_US.utf8"], |tdb| {
+    ///     // insert rows, assert on ORDER BY title ...
+    /// });
+    /// ```
+    pub fn run_with_collations(&self, collations: &[&str], mut f: impl FnMut(&TestDb)) {
+        for collation in collations {
+            let tdb = self.clone().collation(*collation).build();
+            run_reporting(&tdb, &format!("collation {}", collation), &mut f);
+        }
+    }
+
+    /// Run `f` against a fresh database on each of the Postgres major
+    /// `versions`, for libraries that support several of them. Each version
+    /// gets a `postgres:<version>` container of its own (with the
+    /// `testcontainers` feature), unless `$TESTDB_PG<version>_URL`, e.g.
+    /// `$TESTDB_PG15_URL`, points to an existing server. Every other setting
+    /// of this builder is kept, and each server is checked to actually run
+    /// its version.
+    ///
+    /// ```no_run
+    /// use diesel_database_tester::TestDb;
+    ///
+    /// TestDb::builder().run_on_versions(&["13", "15", "16"], |tdb| {
+    ///     // exercise version-dependent queries ...
+    /// });
+    /// ```
+    pub fn run_on_versions(&self, versions: &[&str], f: impl FnMut(&TestDb)) {
+        self.run_on_servers(
+            versions,
+            |version| env::var(format!("TESTDB_PG{}_URL", version)).ok(),
+            f,
+        )
+    }
+
+    /// [`run_on_versions`](Self::run_on_versions) with the URLs of existing
+    /// servers looked up by `server_url` instead of the environment.
+    pub(crate) fn run_on_servers(
+        &self,
+        versions: &[&str],
+        server_url: impl Fn(&str) -> Option<String>,
+        mut f: impl FnMut(&TestDb),
+    ) {
+        for version in versions {
+            let builder = match server_url(version) {
+                Some(url) => self.on_server(&url),
+                None => self.on_container(version),
+            };
+            let tdb = builder.build();
+            let actual = tdb.server_version();
+            assert_eq!(
+                actual.to_string(),
+                *version,
+                "The server for Postgres {} runs Postgres {}",
+                version,
+                actual
+            );
+            run_reporting(&tdb, &format!("Postgres {}", version), &mut f);
+        }
+    }
+
+    /// This builder on the server at `url`.
+    fn on_server(&self, url: &str) -> Self {
+        let server = Self::from_url(url);
+        let mut builder = self.clone();
+        builder.host = server.host;
+        builder.port = server.port;
+        builder.user = server.user.or(builder.user);
+        builder.password = server.password.or(builder.password);
+        builder.sslmode = server.sslmode.or(builder.sslmode);
+        builder.params.extend(server.params);
+        builder
+    }
+
+    #[cfg(feature = "testcontainers")]
+    fn on_container(&self, version: &str) -> Self {
+        let mut builder = self.clone().container();
+        builder.container_tag = Some(version.to_string());
+        builder
+    }
+
+    #[cfg(not(feature = "testcontainers"))]
+    fn on_container(&self, version: &str) -> Self {
+        panic!(
+            "No server for Postgres {}, set TESTDB_PG{}_URL to its URL or enable the \
+             testcontainers feature",
+            version, version
+        )
+    }
+
+    /// Load the SQL scripts at `paths` right after the migrations, see
+    /// [`TestDb::load_fixtures`]. Can be called repeatedly, scripts run in the
+    /// order they were added.
+    pub fn with_fixtures(mut self, paths: impl IntoIterator<Item = impl Into<PathBuf>>) -> Self {
+        self.fixtures.extend(paths.into_iter().map(Into::into));
+        self
+    }
+
+    /// Call `seed` with a connection to the database during setup, after the
+    /// fixtures are loaded, e.g. to insert rows with existing factory
+    /// functions. An error fails the setup like a failing migration. Can be
+    /// called repeatedly, seeds run in the order they were added.
+    ///
+    /// ```no_run
+    /// use diesel::{connection::SimpleConnection, PgConnection};
+    /// use diesel_database_tester::TestDb;
+    ///
+    /// let tdb = TestDb::builder()
+    ///     .with_seed(|conn: &mut PgConnection| {
+    ///         conn.batch_execute("INSERT INTO todos (title) VALUES ('first')")
+    ///     })
+    ///     .build();
+    /// ```
+    pub fn with_seed<E: Into<Box<dyn Error + Send + Sync>>>(
+        mut self,
+        seed: impl Fn(&mut PgConnection) -> Result<(), E> + Send + Sync + 'static,
+    ) -> Self {
+        self.seeds.push(Arc::new(move |conn: &mut PgConnection| {
+            seed(conn).map_err(Into::into)
+        }));
+        self
+    }
+
+    /// Run `hook`, SQL or a closure, on the new database right before its
+    /// migrations, e.g. to create the roles they grant privileges to. Hooks
+    /// run in the order they were added.
+    ///
+    /// Hooks can't be told apart by their contents, so databases of builders
+    /// with hooks are neither cloned from a [shared
+    /// template](Self::shared_template) nor restored from the [schema
+    /// cache](Self::schema_cache).
+    ///
+    /// ```no_run
+    /// use diesel::{connection::SimpleConnection, PgConnection};
+    /// use diesel_database_tester::TestDb;
+    ///
+    /// let tdb = TestDb::builder()
+    ///     .before_migrations("DO $$ BEGIN CREATE ROLE app_reader; \
+    ///         EXCEPTION WHEN duplicate_object THEN NULL; END $$")
+    ///     .after_migrations(|conn: &mut PgConnection| {
+    ///         conn.batch_execute("GRANT SELECT ON ALL TABLES IN SCHEMA public TO app_reader")
+    ///     })
+    ///     .build();
+    /// ```
+    pub fn before_migrations(mut self, hook: impl MigrationHook) -> Self {
+        self.before_migrations.push(Arc::new(hook));
+        self
+    }
+
+    /// Run `hook`, SQL or a closure, on the new database right after its
+    /// migrations, before the fixtures and seeds, e.g. to refresh
+    /// materialized views. See [`before_migrations`](Self::before_migrations).
+    pub fn after_migrations(mut self, hook: impl MigrationHook) -> Self {
+        self.after_migrations.push(Arc::new(hook));
+        self
+    }
+
+    /// Whether the database can be cloned from a shared template.
+    pub(crate) fn uses_shared_template(&self) -> bool {
+        // a template fixes the encoding and locale of its clones
+        self.shared_template && !self.database_options.needs_template0() && !self.has_hooks()
+    }
+
+    /// The schema cache file of this configuration, if it is used.
+    pub(crate) fn schema_cache_path(&self, setup_sql: &[String]) -> Option<PathBuf> {
+        self.schema_cache
+            .as_deref()
+            .filter(|_| !self.has_hooks())
+            .map(|dir| schema_cache::path(dir, &self.migrations, setup_sql))
+    }
+
+    fn has_hooks(&self) -> bool {
+        !self.before_migrations.is_empty() || !self.after_migrations.is_empty()
+    }
+
+    /// Run `ANALYZE` once the database is set up and seeded, so the planner
+    /// starts from realistic statistics.
+    pub fn analyze_after_seed(mut self, analyze: bool) -> Self {
+        self.analyze_after_seed = analyze;
+        self
+    }
+
+    /// Make `settings` the defaults of the database once it is set up and
+    /// seeded, e.g. `("random_page_cost", "1.1")` or planner switches for
+    /// `EXPLAIN` assertions. Since `autovacuum` can only be set server-wide,
+    /// `("autovacuum", "off")` turns it off for every table of the database
+    /// instead, before the [`analyze`](Self::analyze_after_seed), so the
+    /// statistics stay put for the rest of the test:
+    ///
+    /// ```no_run
+    /// # use diesel_database_tester::TestDb;
+    /// let tdb = TestDb::builder()
+    ///     .with_fixtures(["fixtures/orders.sql"])
+    ///     .analyze_after_seed(true)
+    ///     .with_database_settings(&[("autovacuum", "off"), ("jit", "off")])
+    ///     .build();
+    /// ```
+    pub fn with_database_settings(mut self, settings: &[(&str, &str)]) -> Self {
+        self.database_settings.extend(
+            settings
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string())),
+        );
+        self
+    }
+
+    /// Create a login role for the database with only the privileges of
+    /// `role`, and have [`TestDb::pool`] and [`TestDb::url`] connect as it,
+    /// so tests fail on grants production lacks. Needs `CREATEROLE`.
+    pub fn app_role(mut self, role: AppRole) -> Self {
+        self.app_role = Some(role);
+        self
+    }
+
+    /// Limit what tests can do to the database, e.g. [`Quota::strict`].
+    pub fn quota(mut self, quota: Quota) -> Self {
+        self.quota = Some(quota);
+        self
+    }
+
+    /// Cancel statements running longer than `timeout`, so a hanging query
+    /// fails its test with `canceling statement due to statement timeout`.
+    /// Like the other timeouts, this adjusts the [`quota`](Self::quota) set
+    /// so far, so give those first.
+    pub fn statement_timeout(mut self, timeout: Duration) -> Self {
+        self.quota
+            .get_or_insert_with(Quota::default)
+            .statement_timeout = Some(timeout);
+        self
+    }
+
+    /// Give up waiting for a lock after `timeout`, so deadlocking tests fail
+    /// with `canceling statement due to lock timeout`.
+    pub fn lock_timeout(mut self, timeout: Duration) -> Self {
+        self.quota.get_or_insert_with(Quota::default).lock_timeout = Some(timeout);
+        self
+    }
+
+    /// Terminate sessions idling in an open transaction for longer than
+    /// `timeout`, e.g. a test that forgot to commit while holding locks
+    /// others wait on.
+    pub fn idle_in_transaction_timeout(mut self, timeout: Duration) -> Self {
+        self.quota
+            .get_or_insert_with(Quota::default)
+            .idle_in_transaction_session_timeout = Some(timeout);
+        self
+    }
+
+    /// Apply the [`Profile`] registered under `name`: its roles and
+    /// extensions are created before the migrations, its settings become the
+    /// database defaults once set up.
+    pub fn profile(mut self, name: impl Into<String>) -> Self {
+        self.profile = Some(name.into());
+        self
+    }
+
+    /// Create the database and run the migrations.
+    pub fn build(self) -> TestDb {
+        self.try_build().unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Like [`build`](Self::build), returning the error instead of panicking,
+    /// so a harness can report setup failures cleanly.
+    pub fn try_build(self) -> Result<TestDb, TestDbError> {
+        TestDb::create(self)
+    }
+
+    /// Skip `CREATE DATABASE` and run the migrations into `pg_temp` on a single
+    /// connection instead, see [`TempSchemaDb`].
+    pub fn build_temp_schema(self) -> TempSchemaDb {
+        TempSchemaDb::create(&self.connection_config(), &self.migrations)
+    }
+
+    /// Url of the server test databases are created on, with every fallback
+    /// applied, e.g. for tools outside Rust needing the same server.
+    pub fn server_url(&self) -> Result<String, TestDbError> {
+        self.try_connection_config()?
+            .server_url()
+            .map_err(|e| TestDbError::Config(format!("Failed to resolve the server url: {}", e)))
+    }
+
+    /// Url of the database `dbname` on the [server](Self::server_url).
+    pub fn database_url(&self, dbname: &str) -> Result<String, TestDbError> {
+        Ok(connection::with_database(&self.server_url()?, dbname))
+    }
+
+    /// Work out the steps and SQL [`build`](Self::build) and the eventual
+    /// drop would run, without connecting to the server.
+    pub fn dry_run(&self) -> DryRun {
+        DryRun::new(self)
+    }
+
+    pub(crate) fn resolved_profile(&self) -> Result<Option<Profile>, TestDbError> {
+        self.profile.as_deref().map(Profile::lookup).transpose()
+    }
+
+    /// Statements run before the migrations: the roles and extensions of
+    /// `profile`, the extensions, then the custom types.
+    pub(crate) fn setup_sql(&self, profile: Option<&Profile>) -> Vec<String> {
+        let mut setup_sql = profile.map(Profile::setup_sql).unwrap_or_default();
+        setup_sql.extend(
+            self.extensions
+                .iter()
+                .map(|name| sql::create_extension(name)),
+        );
+        setup_sql.extend(self.types.iter().cloned());
+        setup_sql
+    }
+
+    pub(crate) fn database_name(&self) -> String {
+        self.try_database_name().unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub(crate) fn try_database_name(&self) -> Result<String, TestDbError> {
+        self.try_namer()?
+            .name(self.label.as_deref())
+            .map_err(TestDbError::Config)
+    }
+
+    /// The prefix and naming databases get, `TESTDB_PREFIX` resolved.
+    pub(crate) fn try_namer(&self) -> Result<Namer, TestDbError> {
+        let prefix = match &self.prefix {
+            Some(prefix) => prefix.clone(),
+            None => naming::prefix_from_env()
+                .map_err(TestDbError::Config)?
+                .unwrap_or_else(|| naming::DEFAULT_PREFIX.into()),
+        };
+        Ok(Namer {
+            prefix,
+            naming: self.naming,
+            name_with: self.name_with.clone(),
+        })
+    }
+
+    pub(crate) fn connection_config(&self) -> ConnectionConfig {
+        self.try_connection_config()
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub(crate) fn try_connection_config(&self) -> Result<ConnectionConfig, TestDbError> {
+        let service = self.service_params()?;
+        let mut builder = self.clone();
+        let mut expires_at = None;
+        if let Some(provider) = &self.credentials {
+            let credentials = provider
+                .credentials()
+                .map_err(|e| TestDbError::Config(format!("Failed to fetch credentials: {}", e)))?;
+            expires_at = credentials.expires_at;
+            builder.user = builder.user.or(Some(credentials.user));
+            builder.password = builder
+                .password
+                .filter(|password| !password.is_empty())
+                .or(Some(credentials.password));
+        }
+        let mut config = builder.resolve_connection(non_empty_var, &service);
+        if let Some(refresh) = self.refresh.clone().or_else(|| self.credentials.clone()) {
+            let initial = Credentials {
+                user: config.user.clone(),
+                password: config.password.clone(),
+                expires_at,
+            };
+            config.credentials = Some(Arc::new(
+                RefreshingCredentials::new(initial, refresh).keep_user(self.user.clone()),
+            ));
+        }
+        Ok(config)
+    }
+
+    /// The connection settings for a [`dry_run`](Self::dry_run), resolved
+    /// without asking the credential provider: a user it would supply shows
+    /// as [`PROVIDED_USER`].
+    pub(crate) fn planned_connection_config(&self) -> Result<ConnectionConfig, TestDbError> {
+        let service = self.service_params()?;
+        let mut builder = self.clone();
+        if self.credentials.is_some() {
+            builder.user = builder.user.or_else(|| Some(PROVIDED_USER.into()));
+        }
+        Ok(builder.resolve_connection(non_empty_var, &service))
+    }
+
+    fn service_params(&self) -> Result<ServiceParams, TestDbError> {
+        match self.service.clone().or_else(|| non_empty_var("PGSERVICE")) {
+            Some(name) => service::lookup(&name).ok_or_else(|| {
+                TestDbError::Config(format!(r#"Definition of service "{}" not found"#, name))
+            }),
+            None => Ok(ServiceParams::new()),
+        }
+    }
+
+    fn resolve_connection(
+        &self,
+        env: impl Fn(&str) -> Option<String>,
+        service: &ServiceParams,
+    ) -> ConnectionConfig {
+        // explicit settings win over the service definition, which wins over the environment
+        let lookup = |key: &str, var: &str| service.get(key).cloned().or_else(|| env(var));
+        let host = self
+            .host
+            .clone()
+            .or_else(|| lookup("host", "PGHOST"))
+            .unwrap_or_else(|| "localhost".into());
+        let port = self
+            .port
+            .or_else(|| lookup("port", "PGPORT").and_then(|port| port.parse().ok()))
+            .unwrap_or(5432);
+        let user = self
+            .user
+            .clone()
+            .or_else(|| lookup("user", "PGUSER"))
+            .unwrap_or_else(|| "postgres".into());
+        let password = self
+            .password
+            .clone()
+            .filter(|password| !password.is_empty())
+            .or_else(|| lookup("password", "PGPASSWORD"))
+            // the admin connection has no database, libpq defaults it to the user name
+            .or_else(|| pgpass::lookup(&host, port, &user, &user))
+            .unwrap_or_default();
+        let sslmode = self
+            .sslmode
+            .clone()
+            .or_else(|| lookup("sslmode", "PGSSLMODE"));
+        ConnectionConfig {
+            host,
+            port,
+            user,
+            password,
+            sslmode,
+            params: self.params.clone(),
+            transport: self.transport.clone(),
+            credentials: None,
+            session_sql: self
+                .random_seed
+                .map(|seed| format!("SELECT setseed({})", seed))
+                .into_iter()
+                .chain(self.session_sql.iter().cloned())
+                .collect(),
+            trace_sql: self.trace_sql,
+        }
+    }
+}
+
+/// The user shown by a dry run for one the credential provider supplies.
+pub(crate) const PROVIDED_USER: &str = "<from provider>";
+
+fn non_empty_var(key: &str) -> Option<String> {
+    env::var(key).ok().filter(|v| !v.is_empty())
+}
+
+/// Run `f` against `tdb`, naming `variant` (e.g. `collation C`) when it panics
+/// so a failing run of a matrix can be told apart.
+fn run_reporting(tdb: &TestDb, variant: &str, f: &mut impl FnMut(&TestDb)) {
+    diagnostics::log(format_args!(
+        "running against {} with {}",
+        tdb.dbname, variant
+    ));
+    if let Err(panic) = panic::catch_unwind(AssertUnwindSafe(|| f(tdb))) {
+        eprintln!("test failed with {}", variant);
+        panic::resume_unwind(panic);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::StaticCredentials;
+
+    #[test]
+    fn connection_settings_should_fall_back_to_pg_env() {
+        let vars: HashMap<_, _> = [
+            ("PGHOST", "db.internal"),
+            ("PGPORT", "6543"),
+            ("PGUSER", "ci"),
+            ("PGPASSWORD", "secret"),
+            ("PGSSLMODE", "require"),
+        ]
+        .into_iter()
+        .collect();
+        let env = |key: &str| vars.get(key).map(|v| v.to_string());
+
+        let config = TestDbBuilder::new().resolve_connection(env, &ServiceParams::new());
+        assert_eq!(config.host, "db.internal");
+        assert_eq!(config.port, 6543);
+        assert_eq!(config.user, "ci");
+        assert_eq!(config.password, "secret");
+        assert_eq!(config.sslmode.as_deref(), Some("require"));
+
+        let config = TestDbBuilder::new()
+            .host("localhost")
+            .port(15432)
+            .sslmode("disable")
+            .resolve_connection(env, &ServiceParams::new());
+        assert_eq!(config.host, "localhost");
+        assert_eq!(config.port, 15432);
+        assert_eq!(config.user, "ci");
+        assert_eq!(config.sslmode.as_deref(), Some("disable"));
+
+        let service: ServiceParams = [("host", "svc.internal"), ("user", "svc")]
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        let config = TestDbBuilder::new()
+            .user("explicit")
+            .resolve_connection(env, &service);
+        assert_eq!(config.host, "svc.internal");
+        assert_eq!(config.port, 6543);
+        assert_eq!(config.user, "explicit");
+    }
+
+    #[test]
+    fn credential_provider_should_supply_user_and_password() {
+        let builder = TestDbBuilder::new()
+            .user("explicit")
+            .credentials(StaticCredentials::new("vault", "from-vault"));
+        let config = builder.connection_config();
+        assert_eq!(config.user, "explicit");
+        assert_eq!(config.password, "from-vault");
+        assert!(!format!("{:?}", builder).contains("from-vault"));
+    }
+}
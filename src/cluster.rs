@@ -0,0 +1,93 @@
+//! Several test databases set up and torn down as one, for services that
+//! talk to more than one database.
+
+use crate::{Pool, TestDb, TestDbBuilder, TestDbError};
+
+/// Named test databases on the same server, each built from its own
+/// [`TestDbBuilder`], e.g. with a migration set of its own. All of them are
+/// dropped together when the cluster is.
+///
+/// ```no_run
+/// use diesel_database_tester::{TestCluster, TestDb};
+///
+/// let server = TestDb::builder().port(5432);
+/// let cluster = TestCluster::builder()
+///     .database("app", server.clone().migrations_dir("migrations/app"))
+///     .database("analytics", server.migrations_dir("migrations/analytics"))
+///     .build();
+/// let app = cluster.pool("app");
+/// let analytics = cluster.pool("analytics");
+/// ```
+pub struct TestCluster {
+    databases: Vec<(String, TestDb)>,
+}
+
+/// Collects the databases of a [`TestCluster`].
+#[derive(Default)]
+pub struct TestClusterBuilder {
+    databases: Vec<(String, TestDbBuilder)>,
+}
+
+impl TestCluster {
+    pub fn builder() -> TestClusterBuilder {
+        TestClusterBuilder::default()
+    }
+
+    /// The database added as `name`, if any.
+    pub fn get(&self, name: &str) -> Option<&TestDb> {
+        self.databases
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, tdb)| tdb)
+    }
+
+    /// A pool for the database added as `name`.
+    ///
+    /// # Panics
+    ///
+    /// If there's no database `name` or the pool can't be built.
+    pub fn pool(&self, name: &str) -> Pool {
+        self.get(name)
+            .unwrap_or_else(|| panic!("No database {} in the cluster", name))
+            .pool()
+    }
+
+    /// The databases in the order they were added.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &TestDb)> {
+        self.databases
+            .iter()
+            .map(|(name, tdb)| (name.as_str(), tdb))
+    }
+}
+
+impl TestClusterBuilder {
+    /// Add a database called `name` within the cluster, built by `builder`.
+    /// The actual database name embeds `name`, see
+    /// [`TestDbBuilder::named`].
+    pub fn database(mut self, name: impl Into<String>, builder: TestDbBuilder) -> Self {
+        self.databases.push((name.into(), builder));
+        self
+    }
+
+    pub fn build(self) -> TestCluster {
+        self.try_build().unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Like [`build`](Self::build), returning the error instead of
+    /// panicking. The databases built before the failing one are dropped
+    /// again.
+    pub fn try_build(self) -> Result<TestCluster, TestDbError> {
+        let mut databases: Vec<(String, TestDb)> = Vec::with_capacity(self.databases.len());
+        for (name, builder) in self.databases {
+            if databases.iter().any(|(n, _)| *n == name) {
+                return Err(TestDbError::Config(format!(
+                    "Database {} added to the cluster twice",
+                    name
+                )));
+            }
+            let tdb = builder.named(name.as_str()).try_build()?;
+            databases.push((name, tdb));
+        }
+        Ok(TestCluster { databases })
+    }
+}
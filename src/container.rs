@@ -0,0 +1,85 @@
+//! Disposable Postgres servers in docker containers, for runs without a
+//! long-running server such as CI.
+//!
+//! One container per image tag is shared by every [`TestDb`](crate::TestDb)
+//! of the process that asks for it, started on first use once it accepts
+//! connections, and stopped as soon as the last of them is gone.
+
+use std::{
+    collections::HashMap,
+    panic,
+    sync::{Arc, Mutex, OnceLock, Weak},
+    thread,
+};
+
+use log::info;
+use testcontainers_modules::{
+    postgres::Postgres,
+    testcontainers::{runners::SyncRunner, Container, ImageExt, TestcontainersError},
+};
+
+use crate::TestDbError;
+
+/// The credentials the postgres image is started with.
+pub(crate) const USER: &str = "postgres";
+pub(crate) const PASSWORD: &str = "postgres";
+
+/// A running container, stopped when dropped.
+pub(crate) struct SharedContainer {
+    container: Option<Container<Postgres>>,
+    pub host: String,
+    pub port: u16,
+}
+
+/// The running containers by image tag, `None` for the module's default.
+static CURRENT: OnceLock<Mutex<HashMap<Option<String>, Weak<SharedContainer>>>> = OnceLock::new();
+
+/// The running container of the `postgres:<tag>` image, started if no
+/// `TestDb` holds one.
+pub(crate) fn acquire(tag: Option<&str>) -> Result<Arc<SharedContainer>, TestDbError> {
+    let mut current = CURRENT.get_or_init(Default::default).lock().unwrap();
+    let tag = tag.map(str::to_string);
+    if let Some(container) = current.get(&tag).and_then(Weak::upgrade) {
+        return Ok(container);
+    }
+    // the blocking runner drives its own runtime, which can't nest in the caller's
+    let image = tag.clone();
+    let container = thread::spawn(move || start(image.as_deref()))
+        .join()
+        .unwrap_or_else(|panic| panic::resume_unwind(panic))
+        .map(Arc::new)
+        .map_err(TestDbError::Container)?;
+    current.insert(tag, Arc::downgrade(&container));
+    Ok(container)
+}
+
+fn start(tag: Option<&str>) -> Result<SharedContainer, TestcontainersError> {
+    let container = match tag {
+        Some(tag) => {
+            info!("Starting a postgres:{} container", tag);
+            Postgres::default().with_tag(tag).start()?
+        }
+        None => {
+            info!("Starting a Postgres container");
+            Postgres::default().start()?
+        }
+    };
+    let host = container.get_host()?.to_string();
+    let port = container.get_host_port_ipv4(5432)?;
+    info!("Postgres container listening on {}:{}", host, port);
+    Ok(SharedContainer {
+        container: Some(container),
+        host,
+        port,
+    })
+}
+
+impl Drop for SharedContainer {
+    fn drop(&mut self) {
+        if let Some(container) = self.container.take() {
+            info!("Stopping the Postgres container on port {}", self.port);
+            // removing it blocks on the runner's runtime as well
+            let _ = thread::spawn(move || drop(container)).join();
+        }
+    }
+}
@@ -0,0 +1,186 @@
+//! Pluggable sources for the user name and password used to connect.
+
+use std::{
+    error::Error,
+    fmt,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
+};
+
+use log::info;
+
+/// Credentials are refreshed once they are this close to expiring.
+pub(crate) const REFRESH_MARGIN: Duration = Duration::from_secs(60);
+
+/// A user name and password, optionally only valid for a limited time.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Credentials {
+    pub user: String,
+    pub password: String,
+    /// When the password stops being accepted, e.g. for short-lived tokens.
+    pub expires_at: Option<SystemTime>,
+}
+
+impl Credentials {
+    pub fn new(user: impl Into<String>, password: impl Into<String>) -> Self {
+        Self {
+            user: user.into(),
+            password: password.into(),
+            expires_at: None,
+        }
+    }
+
+    /// Mark the credentials as valid for `ttl` from now.
+    pub fn expires_in(mut self, ttl: Duration) -> Self {
+        self.expires_at = Some(SystemTime::now() + ttl);
+        self
+    }
+
+    /// Whether the credentials expire within `margin` from now.
+    pub fn expires_within(&self, margin: Duration) -> bool {
+        self.expires_at
+            .map(|at| at <= SystemTime::now() + margin)
+            .unwrap_or(false)
+    }
+}
+
+impl fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Credentials")
+            .field("user", &self.user)
+            .field("password", &"********")
+            .field("expires_at", &self.expires_at)
+            .finish()
+    }
+}
+
+/// Supplies the credentials used to connect to the server, so they can come
+/// from Vault, SOPS, a cloud secrets manager, ... without forking this crate.
+pub trait CredentialProvider: Send + Sync {
+    fn credentials(&self) -> Result<Credentials, Box<dyn Error + Send + Sync + 'static>>;
+
+    /// Forget cached credentials after the server rejected them, so the next
+    /// [`credentials`](Self::credentials) call fetches new ones.
+    fn invalidate(&self) {}
+}
+
+/// A fixed user name and password.
+#[derive(Debug, Clone)]
+pub struct StaticCredentials(pub Credentials);
+
+impl StaticCredentials {
+    pub fn new(user: impl Into<String>, password: impl Into<String>) -> Self {
+        Self(Credentials::new(user, password))
+    }
+}
+
+impl CredentialProvider for StaticCredentials {
+    fn credentials(&self) -> Result<Credentials, Box<dyn Error + Send + Sync + 'static>> {
+        Ok(self.0.clone())
+    }
+}
+
+impl<F> CredentialProvider for F
+where
+    F: Fn() -> Result<Credentials, Box<dyn Error + Send + Sync + 'static>> + Send + Sync,
+{
+    fn credentials(&self) -> Result<Credentials, Box<dyn Error + Send + Sync + 'static>> {
+        self()
+    }
+}
+
+/// The credentials used for new connections, replaced through a refresh
+/// callback once they are about to expire or the server rejects them.
+pub(crate) struct RefreshingCredentials {
+    current: Mutex<Credentials>,
+    refresh: Arc<dyn CredentialProvider>,
+    /// A user configured explicitly, which wins over the refreshed ones.
+    user: Option<String>,
+}
+
+impl RefreshingCredentials {
+    pub fn new(initial: Credentials, refresh: Arc<dyn CredentialProvider>) -> Self {
+        Self {
+            current: Mutex::new(initial),
+            refresh,
+            user: None,
+        }
+    }
+
+    /// Log in as `user` whatever user the refresh callback returns.
+    pub fn keep_user(mut self, user: Option<String>) -> Self {
+        self.user = user;
+        self
+    }
+
+    fn refreshed(&self) -> Result<Credentials, Box<dyn Error + Send + Sync + 'static>> {
+        let mut credentials = self.refresh.credentials()?;
+        if let Some(user) = &self.user {
+            credentials.user = user.clone();
+        }
+        Ok(credentials)
+    }
+
+    /// Credentials for a new connection, refreshed first if they expire soon.
+    pub fn current(&self) -> Result<Credentials, Box<dyn Error + Send + Sync + 'static>> {
+        let mut current = self.current.lock().unwrap();
+        if current.expires_within(REFRESH_MARGIN) {
+            *current = self.refreshed()?;
+            info!("Refreshed credentials for user {}", current.user);
+        }
+        Ok(current.clone())
+    }
+
+    /// Replace the credentials regardless of their expiry, bypassing any
+    /// cache of the refresh callback.
+    pub fn force_refresh(&self) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+        self.refresh.invalidate();
+        let credentials = self.refreshed()?;
+        info!("Refreshed credentials for user {}", credentials.user);
+        *self.current.lock().unwrap() = credentials;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    #[test]
+    fn expiring_credentials_should_be_refreshed() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let refresh =
+            move || {
+                let n = counter.fetch_add(1, Ordering::SeqCst) + 1;
+                Ok(Credentials::new("iam", format!("token-{}", n))
+                    .expires_in(Duration::from_secs(900)))
+            };
+        let initial = Credentials::new("iam", "token-0").expires_in(Duration::from_secs(30));
+        let credentials = RefreshingCredentials::new(initial, Arc::new(refresh));
+
+        assert_eq!(credentials.current().unwrap().password, "token-1");
+        assert_eq!(credentials.current().unwrap().password, "token-1");
+        credentials.force_refresh().unwrap();
+        assert_eq!(credentials.current().unwrap().password, "token-2");
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn refreshes_should_keep_an_explicit_user() {
+        let refresh = || Ok(Credentials::new("iam", "token-1"));
+        let initial = Credentials::new("app", "token-0").expires_in(Duration::ZERO);
+        let credentials = RefreshingCredentials::new(initial, Arc::new(refresh))
+            .keep_user(Some("app".to_string()));
+
+        let current = credentials.current().unwrap();
+        assert_eq!(
+            (current.user.as_str(), current.password.as_str()),
+            ("app", "token-1")
+        );
+        credentials.force_refresh().unwrap();
+        assert_eq!(credentials.current().unwrap().user, "app");
+    }
+}
@@ -0,0 +1,164 @@
+//! Rolling a database back to an earlier state within one test.
+
+use std::error::Error;
+
+use diesel::{
+    sql_query,
+    sql_types::{Integer, Text},
+    PgConnection, QueryResult, QueryableByName, RunQueryDsl,
+};
+use log::error;
+
+use crate::{
+    connect_to,
+    connection::with_database,
+    diagnostics,
+    fork::{copy_database, execute_disconnected, replace_database, revoke_app_role},
+    sql::{self, quote_ident},
+    TestDb,
+};
+
+/// A copy of a test database's state taken by [`TestDb::snapshot`], kept as
+/// a database of its own until dropped.
+pub struct DatabaseSnapshot {
+    server_url: String,
+    dbname: String,
+}
+
+/// What `CREATE DATABASE ... TEMPLATE` doesn't copy and a restore has to
+/// put back.
+#[derive(QueryableByName)]
+struct Properties {
+    #[diesel(sql_type = Text)]
+    owner: String,
+    #[diesel(sql_type = Integer)]
+    connection_limit: i32,
+}
+
+#[derive(QueryableByName)]
+struct Grant {
+    #[diesel(sql_type = Text)]
+    grantee: String,
+    #[diesel(sql_type = Text)]
+    privilege: String,
+}
+
+impl TestDb {
+    /// Copy the current state of the database, to roll back to it later with
+    /// [`restore`](Self::restore), e.g. between the phases of a long
+    /// integration test.
+    ///
+    /// Like [`fork`](Self::fork), this terminates every open connection to
+    /// the database first.
+    ///
+    /// ```no_run
+    /// # use diesel_database_tester::TestDb;
+    /// # fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    /// let tdb = TestDb::builder().build();
+    /// // ... set up the midpoint ...
+    /// let snap = tdb.snapshot()?;
+    /// // ... first scenario ...
+    /// tdb.restore(&snap)?;
+    /// // ... second scenario, starting from the midpoint again ...
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn snapshot(&self) -> Result<DatabaseSnapshot, Box<dyn Error + Send + Sync>> {
+        let server_url = self.server_url();
+        let mut conn = connect_to(&server_url)?;
+        let dbname = self.namer.name(Some("snap"))?;
+        diagnostics::log(format_args!("snapshotting {} as {}", self.dbname, dbname));
+        copy_database(&mut conn, &self.dbname, &dbname)?;
+        let snapshot = DatabaseSnapshot { server_url, dbname };
+        if self.app_password.is_some() {
+            revoke_app_role(&snapshot.server_url, &snapshot.dbname, &self.dbname)?;
+        }
+        Ok(snapshot)
+    }
+
+    /// Replace the database with a copy of `snapshot`, which stays usable for
+    /// further restores. Open connections are terminated; pools reconnect on
+    /// their next checkout. The database is only dropped once the copy is
+    /// complete, so a failed restore leaves it as it was.
+    pub fn restore(&self, snapshot: &DatabaseSnapshot) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let mut conn = connect_to(&self.server_url())?;
+        diagnostics::log(format_args!(
+            "restoring {} from {}",
+            self.dbname, snapshot.dbname
+        ));
+        let properties: Properties = sql_query(
+            "SELECT pg_get_userbyid(datdba)::text AS owner, datconnlimit AS connection_limit \
+             FROM pg_database WHERE datname = $1",
+        )
+        .bind::<Text, _>(&self.dbname)
+        .get_result(&mut conn)?;
+        let grants = grants(&mut conn, &self.dbname)?;
+        let staging = self.namer.name(Some("restore"))?;
+        replace_database(&mut conn, &snapshot.dbname, &self.dbname, &staging)?;
+        diagnostics::execute(
+            &mut conn,
+            &format!(
+                "ALTER DATABASE {} OWNER TO {}",
+                quote_ident(&self.dbname),
+                quote_ident(&properties.owner)
+            ),
+        )?;
+        if let Ok(limit) = u32::try_from(properties.connection_limit) {
+            diagnostics::execute(&mut conn, &sql::connection_limit(&self.dbname, limit))?;
+        }
+        for Grant { grantee, privilege } in grants {
+            diagnostics::execute(
+                &mut conn,
+                &format!(
+                    "GRANT {} ON DATABASE {} TO {}",
+                    privilege,
+                    quote_ident(&self.dbname),
+                    grantee
+                ),
+            )?;
+        }
+        if let Some(role) = &self.app_role {
+            // the snapshot doesn't carry the role's privileges, see `snapshot`
+            let mut conn = connect_to(&with_database(&self.server_url(), &self.dbname))?;
+            for sql in role.privileges_sql(&self.dbname) {
+                diagnostics::execute(&mut conn, &sql)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The privileges on `dbname` granted to others than its owner.
+fn grants(conn: &mut PgConnection, dbname: &str) -> QueryResult<Vec<Grant>> {
+    sql_query(
+        "SELECT CASE WHEN a.grantee = 0 THEN 'PUBLIC' \
+         ELSE quote_ident(pg_get_userbyid(a.grantee)) END AS grantee, \
+         a.privilege_type::text AS privilege \
+         FROM pg_database d, aclexplode(d.datacl) a \
+         WHERE d.datname = $1 AND a.grantee <> d.datdba",
+    )
+    .bind::<Text, _>(dbname)
+    .load(conn)
+}
+
+impl DatabaseSnapshot {
+    /// The name of the database holding the snapshot.
+    pub fn dbname(&self) -> &str {
+        &self.dbname
+    }
+}
+
+impl Drop for DatabaseSnapshot {
+    fn drop(&mut self) {
+        let dropped = connect_to(&self.server_url)
+            .map_err(|e| e.to_string())
+            .and_then(|mut conn| {
+                execute_disconnected(&mut conn, &self.dbname, &sql::drop_database(&self.dbname))
+                    .map_err(|e| e.to_string())
+            });
+        if let Err(e) = dropped {
+            // best effort, stale cleanup removes it eventually
+            error!("Error while dropping snapshot {}: {}", self.dbname, e);
+        }
+    }
+}
@@ -0,0 +1,48 @@
+//! Verbose diagnostics for setup failures, enabled with `TESTDB_DEBUG=1`.
+//!
+//! Everything is also sent to the `log` facade at debug level; with the env
+//! var set it is printed to stderr as well, so it shows up in `cargo test`
+//! output without configuring a logger.
+
+use std::{env, fmt::Display, sync::OnceLock, time::Instant};
+
+use diesel::{PgConnection, QueryResult, RunQueryDsl};
+use log::debug;
+
+use crate::trace;
+
+static ENABLED: OnceLock<bool> = OnceLock::new();
+
+pub(crate) fn enabled() -> bool {
+    *ENABLED.get_or_init(|| {
+        env::var("TESTDB_DEBUG")
+            .map(|v| !matches!(v.as_str(), "" | "0" | "false"))
+            .unwrap_or(false)
+    })
+}
+
+pub(crate) fn log(message: impl Display) {
+    debug!("{}", message);
+    if enabled() {
+        eprintln!("[testdb] {}", message);
+    }
+}
+
+/// Run one setup phase, logging when it starts and how long it took.
+pub(crate) fn phase<T>(name: &str, f: impl FnOnce() -> T) -> T {
+    let _span = trace::phase(name);
+    log(format_args!("{}...", name));
+    let start = Instant::now();
+    let result = f();
+    log(format_args!("{} done in {:?}", name, start.elapsed()));
+    result
+}
+
+/// Execute `sql`, logging the full statement and why it failed.
+pub(crate) fn execute(conn: &mut PgConnection, sql: &str) -> QueryResult<usize> {
+    log(format_args!("executing: {}", sql));
+    diesel::sql_query(sql).execute(conn).map_err(|e| {
+        log(format_args!("statement failed: {}\n  error: {}", sql, e));
+        e
+    })
+}
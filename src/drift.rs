@@ -0,0 +1,408 @@
+//! Comparing the migrated database with the tables Diesel was told about, to
+//! catch a migration that changed a table without `schema.rs` being
+//! regenerated.
+
+use std::{collections::BTreeMap, error::Error, fmt, fs::read_to_string, path::Path};
+
+use diesel::{
+    sql_query,
+    sql_types::{Bool, Text},
+    QueryableByName, RunQueryDsl,
+};
+
+use crate::TestDb;
+
+/// A column as compared: its Postgres type name and whether it takes NULL.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ColumnType {
+    /// `pg_type.typname`, e.g. `int4` or `_text` for `text[]`.
+    name: String,
+    nullable: bool,
+}
+
+impl fmt::Display for ColumnType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.nullable {
+            write!(f, "Nullable<{}>", self.name)
+        } else {
+            f.write_str(&self.name)
+        }
+    }
+}
+
+type Tables = BTreeMap<String, BTreeMap<String, ColumnType>>;
+
+/// The tables and columns the database is expected to have, read from a
+/// Diesel `schema.rs` or listed by hand.
+///
+/// Tables outside the database's current schema are keyed by their
+/// qualified name, as in `table! { audit.events { .. } }`.
+#[derive(Debug, Clone, Default)]
+pub struct ExpectedSchema {
+    tables: Tables,
+}
+
+impl ExpectedSchema {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Expect `table` with `columns`, given as names and Diesel SQL types,
+    /// e.g. `("title", "Varchar")` or `("tags", "Nullable<Array<Text>>")`.
+    pub fn table(mut self, table: impl Into<String>, columns: &[(&str, &str)]) -> Self {
+        let columns = columns
+            .iter()
+            .map(|(name, sql_type)| (name.to_string(), column_type(sql_type)))
+            .collect();
+        self.tables.insert(table.into(), columns);
+        self
+    }
+
+    /// Read the `table!` definitions of a `schema.rs` as written by
+    /// `diesel print-schema`; joins and everything else are ignored.
+    pub fn from_schema_rs(source: &str) -> Result<Self, String> {
+        let mut tables = Tables::new();
+        let mut table: Option<(String, BTreeMap<String, ColumnType>)> = None;
+        let mut sql_name = None;
+        for (number, line) in source.lines().enumerate() {
+            let line = line.trim();
+            if let Some(name) = line
+                .strip_prefix("#[sql_name = \"")
+                .and_then(|rest| rest.strip_suffix("\"]"))
+            {
+                sql_name = Some(name.to_string());
+                continue;
+            }
+            if line.is_empty() || line.starts_with("//") || line.starts_with('#') {
+                continue;
+            }
+            match &mut table {
+                None => {
+                    // the table header, e.g. `todos (id) {`
+                    let Some(header) = line.strip_suffix('{') else {
+                        continue;
+                    };
+                    let end = header
+                        .find(|c: char| !(c.is_alphanumeric() || "_.#".contains(c)))
+                        .unwrap_or(header.len());
+                    let (name, rest) = header.split_at(end);
+                    let rest = rest.trim();
+                    if name.is_empty() || !(rest.is_empty() || rest.starts_with('(')) {
+                        continue;
+                    }
+                    let name = sql_name.take().unwrap_or_else(|| unraw(name));
+                    table = Some((name, BTreeMap::new()));
+                }
+                Some((name, columns)) => {
+                    if line == "}" {
+                        let (name, columns) = (std::mem::take(name), std::mem::take(columns));
+                        tables.insert(name, columns);
+                        table = None;
+                    } else if let Some((column, sql_type)) = line.split_once("->") {
+                        let column = sql_name.take().unwrap_or_else(|| unraw(column.trim()));
+                        let sql_type = sql_type.trim().trim_end_matches(',');
+                        columns.insert(column, column_type(sql_type));
+                    } else if !line.starts_with("use ") {
+                        return Err(format!(
+                            "Unexpected line {} in table {}: {}",
+                            number + 1,
+                            name,
+                            line
+                        ));
+                    }
+                }
+            }
+        }
+        match table {
+            Some((name, _)) => Err(format!("Table {} is never closed", name)),
+            None => Ok(ExpectedSchema { tables }),
+        }
+    }
+
+    /// Like [`from_schema_rs`](Self::from_schema_rs), reading the file at
+    /// `path`.
+    pub fn from_schema_file(path: impl AsRef<Path>) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let path = path.as_ref();
+        let source = read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        Ok(Self::from_schema_rs(&source)
+            .map_err(|e| format!("Failed to parse {}: {}", path.display(), e))?)
+    }
+}
+
+/// The differences found by [`TestDb::verify_schema`], one per line when
+/// displayed: `+` for what only the database has, `-` for what only the
+/// expected schema has and `~` for columns whose type differs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchemaDrift {
+    pub dbname: String,
+    pub differences: Vec<String>,
+}
+
+impl fmt::Display for SchemaDrift {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Schema of {} differs from the expected one:",
+            self.dbname
+        )?;
+        for difference in &self.differences {
+            write!(f, "\n  {}", difference)?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for SchemaDrift {}
+
+#[derive(QueryableByName)]
+struct Column {
+    #[diesel(sql_type = Text)]
+    table_name: String,
+    #[diesel(sql_type = Text)]
+    column_name: String,
+    #[diesel(sql_type = Text)]
+    udt_name: String,
+    #[diesel(sql_type = Bool)]
+    nullable: bool,
+}
+
+impl TestDb {
+    /// Compare the tables of the migrated database with `expected`, failing
+    /// with a [`SchemaDrift`] listing every difference in table names, column
+    /// names, types and nullability.
+    ///
+    /// ```no_run
+    /// # use diesel_database_tester::{ExpectedSchema, TestDb};
+    /// let tdb = TestDb::builder().build();
+    /// let expected = ExpectedSchema::from_schema_file("src/schema.rs").unwrap();
+    /// if let Err(drift) = tdb.verify_schema(&expected) {
+    ///     panic!("{}\nrun `diesel print-schema` to update src/schema.rs", drift);
+    /// }
+    /// ```
+    pub fn verify_schema(
+        &self,
+        expected: &ExpectedSchema,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let columns: Vec<Column> = sql_query(
+            "SELECT CASE WHEN c.table_schema = current_schema() THEN c.table_name::text \
+             ELSE c.table_schema || '.' || c.table_name END AS table_name, \
+             c.column_name::text AS column_name, c.udt_name::text AS udt_name, \
+             c.is_nullable = 'YES' AS nullable \
+             FROM information_schema.columns c \
+             JOIN information_schema.tables t \
+             ON t.table_schema = c.table_schema AND t.table_name = c.table_name \
+             WHERE t.table_type = 'BASE TABLE' \
+             AND c.table_schema NOT IN ('pg_catalog', 'information_schema') \
+             AND c.table_schema NOT LIKE 'pg_temp%' \
+             AND c.table_name <> '__diesel_schema_migrations'",
+        )
+        .load(&mut self.connect())?;
+        let mut actual = Tables::new();
+        for column in columns {
+            actual.entry(column.table_name).or_default().insert(
+                column.column_name,
+                ColumnType {
+                    name: column.udt_name,
+                    nullable: column.nullable,
+                },
+            );
+        }
+        let differences = diff(&expected.tables, &actual);
+        if differences.is_empty() {
+            Ok(())
+        } else {
+            Err(SchemaDrift {
+                dbname: self.dbname.clone(),
+                differences,
+            }
+            .into())
+        }
+    }
+}
+
+fn diff(expected: &Tables, actual: &Tables) -> Vec<String> {
+    let mut differences = Vec::new();
+    for (table, columns) in expected {
+        let Some(actual_columns) = actual.get(table) else {
+            differences.push(format!("- table {}", table));
+            continue;
+        };
+        for (column, column_type) in columns {
+            match actual_columns.get(column) {
+                None => differences.push(format!("- {}.{} {}", table, column, column_type)),
+                Some(actual_type) if actual_type != column_type => differences.push(format!(
+                    "~ {}.{}: expected {}, found {}",
+                    table, column, column_type, actual_type
+                )),
+                Some(_) => {}
+            }
+        }
+        for (column, column_type) in actual_columns {
+            if !columns.contains_key(column) {
+                differences.push(format!("+ {}.{} {}", table, column, column_type));
+            }
+        }
+    }
+    for table in actual.keys() {
+        if !expected.contains_key(table) {
+            differences.push(format!("+ table {}", table));
+        }
+    }
+    differences
+}
+
+/// `r#type` is the column `type`.
+fn unraw(ident: &str) -> String {
+    ident.trim_start_matches("r#").to_string()
+}
+
+/// The Postgres type a Diesel SQL type like `Nullable<Array<Int4>>` maps to.
+fn column_type(sql_type: &str) -> ColumnType {
+    let sql_type: String = sql_type.chars().filter(|c| !c.is_whitespace()).collect();
+    match generic_argument(&sql_type, "Nullable") {
+        Some(inner) => ColumnType {
+            name: type_name(inner),
+            nullable: true,
+        },
+        None => ColumnType {
+            name: type_name(&sql_type),
+            nullable: false,
+        },
+    }
+}
+
+/// `T` of `wrapper<T>`, the wrapper possibly given by its path.
+fn generic_argument<'a>(sql_type: &'a str, wrapper: &str) -> Option<&'a str> {
+    let (path, rest) = sql_type.split_once('<')?;
+    let name = path.rsplit("::").next().unwrap_or(path);
+    (name == wrapper).then(|| rest.strip_suffix('>')).flatten()
+}
+
+fn type_name(sql_type: &str) -> String {
+    if let Some(element) = generic_argument(sql_type, "Array") {
+        let element = generic_argument(element, "Nullable").unwrap_or(element);
+        return format!("_{}", type_name(element));
+    }
+    let name = sql_type.rsplit("::").next().unwrap_or(sql_type);
+    let alias = match name {
+        "SmallInt" | "Smallint" => "int2",
+        "Integer" => "int4",
+        "BigInt" | "Bigint" => "int8",
+        "Float" => "float4",
+        "Double" => "float8",
+        "Decimal" => "numeric",
+        "VarChar" => "varchar",
+        "Binary" => "bytea",
+        "MacAddr" => "macaddr",
+        "MacAddr8" => "macaddr8",
+        "Timestamptz" => "timestamptz",
+        _ => "",
+    };
+    if !alias.is_empty() {
+        return alias.to_string();
+    }
+    // custom types are generated in CamelCase from the type name
+    let mut snake = String::new();
+    for (i, c) in name.chars().enumerate() {
+        if c.is_ascii_uppercase() && i > 0 {
+            snake.push('_');
+        }
+        snake.push(c.to_ascii_lowercase());
+    }
+    snake
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diesel_types_should_map_to_postgres_names() {
+        let cases = [
+            ("Int4", "int4", false),
+            ("Nullable<Bool>", "bool", true),
+            ("diesel::sql_types::BigInt", "int8", false),
+            ("Array<Nullable<Text>>", "_text", false),
+            ("Nullable < Array<Int4> >", "_int4", true),
+            ("crate::schema::sql_types::TodoState", "todo_state", false),
+            ("Timestamptz", "timestamptz", false),
+        ];
+        for (sql_type, name, nullable) in cases {
+            let expected = ColumnType {
+                name: name.to_string(),
+                nullable,
+            };
+            assert_eq!(column_type(sql_type), expected, "{}", sql_type);
+        }
+    }
+
+    #[test]
+    fn schema_rs_should_parse_tables_and_renamed_columns() {
+        let expected = ExpectedSchema::from_schema_rs(
+            r#"
+            // @generated automatically by Diesel CLI.
+
+            pub mod sql_types {
+                #[derive(diesel::sql_types::SqlType)]
+                #[diesel(postgres_type(name = "mood"))]
+                pub struct Mood;
+            }
+
+            diesel::table! {
+                use diesel::sql_types::*;
+                use super::sql_types::Mood;
+
+                /// Doc comments are skipped.
+                users (id) {
+                    id -> Int4,
+                    #[sql_name = "type"]
+                    type_ -> Nullable<Mood>,
+                    r#ref -> Varchar,
+                }
+            }
+
+            diesel::table! {
+                audit.events {
+                    id -> Int8,
+                }
+            }
+
+            diesel::joinable!(events -> users (id));
+            diesel::allow_tables_to_appear_in_same_query!(events, users,);
+            "#,
+        )
+        .unwrap();
+        let users = &expected.tables["users"];
+        assert_eq!(users.keys().collect::<Vec<_>>(), ["id", "ref", "type"]);
+        assert_eq!(users["type"].to_string(), "Nullable<mood>");
+        assert_eq!(expected.tables["audit.events"]["id"].name, "int8");
+        assert!(ExpectedSchema::from_schema_rs("table! {\n users (id) {\n id -> Int4,\n").is_err());
+    }
+
+    #[test]
+    fn diff_should_list_every_difference() {
+        let expected = ExpectedSchema::new()
+            .table(
+                "todos",
+                &[("id", "Int4"), ("title", "Text"), ("done", "Bool")],
+            )
+            .table("users", &[("id", "Int4")]);
+        let actual = ExpectedSchema::new()
+            .table(
+                "todos",
+                &[("id", "Int4"), ("title", "Nullable<Text>"), ("due", "Date")],
+            )
+            .table("tags", &[("id", "Int4")]);
+        assert_eq!(
+            diff(&expected.tables, &actual.tables),
+            [
+                "- todos.done bool",
+                "~ todos.title: expected text, found Nullable<text>",
+                "+ todos.due date",
+                "- table users",
+                "+ table tags",
+            ]
+        );
+    }
+}
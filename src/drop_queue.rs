@@ -0,0 +1,93 @@
+//! Background queue used to tear down test databases off the caller's thread.
+//!
+//! A `TestDb` owned by an async test is dropped on a tokio worker thread, where
+//! blocking on a spawned thread (or building a nested runtime) would stall or
+//! panic the executor. Such drops are handed to a single long-lived worker
+//! thread instead, and any pending work is flushed when the process exits.
+
+use std::{
+    sync::{mpsc, Condvar, Mutex, OnceLock, PoisonError},
+    thread,
+    time::{Duration, Instant},
+};
+
+use log::warn;
+
+type Job = Box<dyn FnOnce() + Send + 'static>;
+
+/// How long the exit hook waits for queued drops before giving up.
+const EXIT_FLUSH_TIMEOUT: Duration = Duration::from_secs(30);
+
+struct DropQueue {
+    sender: mpsc::Sender<Job>,
+    pending: Mutex<usize>,
+    idle: Condvar,
+}
+
+static QUEUE: OnceLock<DropQueue> = OnceLock::new();
+
+fn queue() -> &'static DropQueue {
+    QUEUE.get_or_init(|| {
+        let (sender, receiver) = mpsc::channel::<Job>();
+        thread::Builder::new()
+            .name("testdb-drop".into())
+            .spawn(move || {
+                for job in receiver {
+                    if std::panic::catch_unwind(std::panic::AssertUnwindSafe(job)).is_err() {
+                        warn!("Background drop of a test database panicked");
+                    }
+                    let queue = queue();
+                    let mut pending = queue.pending.lock().unwrap_or_else(PoisonError::into_inner);
+                    *pending -= 1;
+                    queue.idle.notify_all();
+                }
+            })
+            .expect("Failed to spawn drop queue thread");
+
+        extern "C" fn flush_on_exit() {
+            if !wait_for_pending_drops(EXIT_FLUSH_TIMEOUT) {
+                warn!("Timed out waiting for test databases to be dropped");
+            }
+        }
+        unsafe {
+            libc::atexit(flush_on_exit);
+        }
+
+        DropQueue {
+            sender,
+            pending: Mutex::new(0),
+            idle: Condvar::new(),
+        }
+    })
+}
+
+/// Schedule `job` to run on the background drop thread.
+pub(crate) fn enqueue(job: impl FnOnce() + Send + 'static) {
+    let queue = queue();
+    *queue.pending.lock().unwrap_or_else(PoisonError::into_inner) += 1;
+    if let Err(mpsc::SendError(job)) = queue.sender.send(Box::new(job)) {
+        // the worker is gone, fall back to running it inline
+        job();
+        *queue.pending.lock().unwrap_or_else(PoisonError::into_inner) -= 1;
+    }
+}
+
+/// Block until every queued drop has finished or `timeout` elapses.
+///
+/// Returns `true` if the queue is empty. Async tests can call this at the end
+/// of a run to make sure their databases are gone before the process exits.
+pub fn wait_for_pending_drops(timeout: Duration) -> bool {
+    let Some(queue) = QUEUE.get() else {
+        return true;
+    };
+    let deadline = Instant::now() + timeout;
+    let mut pending = queue.pending.lock().unwrap();
+    while *pending > 0 {
+        let now = Instant::now();
+        if now >= deadline {
+            return false;
+        }
+        pending = queue.idle.wait_timeout(pending, deadline - now).unwrap().0;
+    }
+    true
+}
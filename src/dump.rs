@@ -0,0 +1,133 @@
+//! Deterministic dumps of table contents, for golden files and snapshot
+//! tests, e.g. with `insta::assert_snapshot!(tdb.dump(&["todos"]))`.
+
+use std::error::Error;
+
+use diesel::{
+    dsl::sql, select, sql_query, sql_types::Text, PgConnection, QueryResult, QueryableByName,
+    RunQueryDsl,
+};
+use serde_json::{Map, Value};
+
+use crate::{
+    sql::{quote_ident, quote_literal},
+    TestDb,
+};
+
+#[derive(QueryableByName)]
+struct Name {
+    #[diesel(sql_type = Text)]
+    name: String,
+}
+
+#[derive(QueryableByName)]
+struct Line {
+    #[diesel(sql_type = Text)]
+    line: String,
+}
+
+impl TestDb {
+    /// The rows of `tables` as pretty-printed JSON, an object of arrays keyed
+    /// by table name. Rows are ordered by primary key, or by all their
+    /// columns without one, and keys are sorted, so the output only changes
+    /// with the data.
+    ///
+    /// ```no_run
+    /// # use diesel_database_tester::TestDb;
+    /// let tdb = TestDb::builder().build();
+    /// // ... run the data migration under test ...
+    /// let expected = std::fs::read_to_string("tests/golden/todos.json").unwrap();
+    /// assert_eq!(tdb.dump(&["todos"]), expected);
+    /// ```
+    pub fn dump(&self, tables: &[&str]) -> String {
+        self.try_dump(tables)
+            .unwrap_or_else(|e| panic!("Failed to dump {:?}: {}", tables, e))
+    }
+
+    /// Like [`dump`](Self::dump), returning the error instead of panicking.
+    pub fn try_dump(&self, tables: &[&str]) -> Result<String, Box<dyn Error + Send + Sync>> {
+        let mut conn = self.try_connect()?;
+        let mut dump = Map::new();
+        for table in tables {
+            let rows: String = select(sql::<Text>(&format!(
+                "(SELECT coalesce(json_agg(t ORDER BY {}), '[]')::text FROM {} t)",
+                order_by(&mut conn, table)?,
+                quote_ident(table)
+            )))
+            .get_result(&mut conn)?;
+            let rows: Value = serde_json::from_str(&rows)?;
+            dump.insert(table.to_string(), rows);
+        }
+        let mut json = serde_json::to_string_pretty(&dump).expect("JSON values always serialize");
+        json.push('\n');
+        Ok(json)
+    }
+
+    /// The rows of `tables` as `INSERT` statements, one per line and row,
+    /// ordered like [`dump`](Self::dump). Values are quoted literals, so the
+    /// statements load back into the same schema.
+    pub fn dump_sql(&self, tables: &[&str]) -> String {
+        self.try_dump_sql(tables)
+            .unwrap_or_else(|e| panic!("Failed to dump {:?}: {}", tables, e))
+    }
+
+    /// Like [`dump_sql`](Self::dump_sql), returning the error instead of
+    /// panicking.
+    pub fn try_dump_sql(&self, tables: &[&str]) -> Result<String, Box<dyn Error + Send + Sync>> {
+        let mut conn = self.try_connect()?;
+        let mut dump = String::new();
+        for table in tables {
+            let columns: Vec<String> = sql_query(
+                "SELECT attname::text AS name FROM pg_attribute \
+                 WHERE attrelid = $1::regclass AND attnum > 0 AND NOT attisdropped \
+                 AND attgenerated = '' ORDER BY attnum",
+            )
+            .bind::<Text, _>(quote_ident(table))
+            .load::<Name>(&mut conn)?
+            .into_iter()
+            .map(|column| quote_ident(&column.name))
+            .collect();
+            let values = columns
+                .iter()
+                .map(|column| format!("quote_nullable(t.{})", column))
+                .collect::<Vec<_>>()
+                .join(", ");
+            let lines: Vec<Line> = sql_query(format!(
+                "SELECT format('INSERT INTO %s (%s) VALUES (%s);', {}, {}, concat_ws(', ', {})) \
+                 AS line FROM {} t ORDER BY {}",
+                quote_literal(&quote_ident(table)),
+                quote_literal(&columns.join(", ")),
+                values,
+                quote_ident(table),
+                order_by(&mut conn, table)?
+            ))
+            .load(&mut conn)?;
+            for Line { line } in lines {
+                dump.push_str(&line);
+                dump.push('\n');
+            }
+        }
+        Ok(dump)
+    }
+}
+
+/// The `ORDER BY` list making the order of `table`'s rows, aliased `t`,
+/// deterministic: its primary key, or the whole row.
+fn order_by(conn: &mut PgConnection, table: &str) -> QueryResult<String> {
+    let key: Vec<Name> = sql_query(
+        "SELECT a.attname::text AS name FROM pg_index i \
+         JOIN pg_attribute a ON a.attrelid = i.indrelid AND a.attnum = ANY(i.indkey) \
+         WHERE i.indrelid = $1::regclass AND i.indisprimary \
+         ORDER BY array_position(i.indkey::int2[], a.attnum)",
+    )
+    .bind::<Text, _>(quote_ident(table))
+    .load(conn)?;
+    if key.is_empty() {
+        return Ok("t::text".to_string());
+    }
+    Ok(key
+        .iter()
+        .map(|column| format!("t.{}", quote_ident(&column.name)))
+        .collect::<Vec<_>>()
+        .join(", "))
+}
@@ -0,0 +1,139 @@
+//! Throwaway Postgres servers run from the local Postgres binaries, for
+//! machines without a running server or docker.
+//!
+//! Like the [container](crate::TestDbBuilder::container), one server is
+//! shared by every [`TestDb`](crate::TestDb) of the process that asks for it:
+//! `initdb` into a temporary directory and a postmaster on a free port on
+//! first use, stopped and deleted as soon as the last of them is gone. The
+//! binaries are taken from `$TESTDB_PG_BIN_DIR`, else from the `PATH`.
+
+use std::{
+    env, fs, io,
+    net::TcpListener,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    sync::{Arc, Mutex, OnceLock, PoisonError, Weak},
+};
+
+use log::{error, info};
+
+use crate::TestDbError;
+
+/// The superuser `initdb` creates. Connections are trusted, the password is
+/// only there for the connection settings.
+pub(crate) const USER: &str = "postgres";
+pub(crate) const PASSWORD: &str = "postgres";
+
+/// A running server, stopped and deleted when dropped.
+pub(crate) struct SharedServer {
+    dir: PathBuf,
+    pub port: u16,
+}
+
+static CURRENT: OnceLock<Mutex<Weak<SharedServer>>> = OnceLock::new();
+
+/// The running server, started if no `TestDb` holds one.
+pub(crate) fn acquire() -> Result<Arc<SharedServer>, TestDbError> {
+    let mut current = CURRENT
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner);
+    if let Some(server) = current.upgrade() {
+        return Ok(server);
+    }
+    let server = Arc::new(start().map_err(TestDbError::EmbeddedServer)?);
+    *current = Arc::downgrade(&server);
+    Ok(server)
+}
+
+fn start() -> io::Result<SharedServer> {
+    let dir = env::temp_dir().join(format!(
+        "testdb-pg-{}-{}",
+        std::process::id(),
+        uuid::Uuid::new_v4().simple()
+    ));
+    fs::create_dir_all(&dir)?;
+    // from here on, dropping it cleans up whatever was started
+    let mut server = SharedServer { dir, port: 0 };
+    info!("Initializing a Postgres server in {}", server.dir.display());
+    run(Command::new(binary("initdb"))
+        .arg("--pgdata")
+        .arg(server.data())
+        .args(["--username", USER, "--auth", "trust", "--encoding", "UTF8"])
+        .arg("--no-sync"))?;
+    server.port = free_port()?;
+    let options = format!(
+        "-p {} -k {} -c listen_addresses=127.0.0.1 \
+         -c fsync=off -c synchronous_commit=off -c full_page_writes=off",
+        server.port,
+        server.dir.display()
+    );
+    run(Command::new(binary("pg_ctl"))
+        .arg("start")
+        .arg("--pgdata")
+        .arg(server.data())
+        .arg("--log")
+        .arg(server.dir.join("postgres.log"))
+        .args(["--wait", "--options", &options]))?;
+    info!("Postgres server listening on 127.0.0.1:{}", server.port);
+    Ok(server)
+}
+
+impl SharedServer {
+    fn data(&self) -> PathBuf {
+        self.dir.join("data")
+    }
+}
+
+impl Drop for SharedServer {
+    fn drop(&mut self) {
+        if self.port != 0 && self.data().join("postmaster.pid").exists() {
+            info!("Stopping the Postgres server on port {}", self.port);
+            let stopped = run(Command::new(binary("pg_ctl"))
+                .arg("stop")
+                .arg("--pgdata")
+                .arg(self.data())
+                .args(["--mode", "immediate", "--wait"]));
+            if let Err(e) = stopped {
+                error!(
+                    "Failed to stop the Postgres server on port {}: {}",
+                    self.port, e
+                );
+                return;
+            }
+        }
+        if let Err(e) = fs::remove_dir_all(&self.dir) {
+            error!("Failed to remove {}: {}", self.dir.display(), e);
+        }
+    }
+}
+
+/// `name` in `$TESTDB_PG_BIN_DIR`, or to be looked up in the `PATH`.
+fn binary(name: &str) -> PathBuf {
+    match env::var_os("TESTDB_PG_BIN_DIR") {
+        Some(dir) => Path::new(&dir).join(name),
+        None => name.into(),
+    }
+}
+
+/// Run `command` to completion, failing with its output unless it succeeds.
+fn run(command: &mut Command) -> io::Result<()> {
+    let output = command
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|e| io::Error::new(e.kind(), format!("{:?}: {}", command.get_program(), e)))?;
+    if output.status.success() {
+        return Ok(());
+    }
+    Err(io::Error::other(format!(
+        "{:?} failed with {}: {}",
+        command.get_program(),
+        output.status,
+        String::from_utf8_lossy(&output.stderr).trim()
+    )))
+}
+
+/// A port nothing listens on right now.
+fn free_port() -> io::Result<u16> {
+    Ok(TcpListener::bind("127.0.0.1:0")?.local_addr()?.port())
+}
@@ -0,0 +1,111 @@
+//! The error returned by the fallible constructors.
+
+use std::{error::Error, fmt};
+
+use diesel::{
+    r2d2::PoolError,
+    result::{ConnectionError, Error as DieselError},
+};
+
+type BoxError = Box<dyn Error + Send + Sync + 'static>;
+
+/// Why a test database or its pool couldn't be set up, returned by
+/// [`TestDb::try_new`](crate::TestDb::try_new),
+/// [`TestDbBuilder::try_build`](crate::TestDbBuilder::try_build) and
+/// [`TestDb::try_pool`](crate::TestDb::try_pool). The panicking constructors
+/// panic with its message.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum TestDbError {
+    /// The configuration couldn't be resolved, e.g. an unknown service, a
+    /// failing credential provider or an unreadable migrations directory.
+    Config(String),
+    /// Connecting to the server or the test database failed. `url` has its
+    /// password redacted.
+    Connect {
+        url: String,
+        source: ConnectionError,
+    },
+    /// The server is older than the builder's
+    /// [`minimum_version`](crate::TestDbBuilder::minimum_version).
+    UnsupportedVersion { actual: u32, minimum: u32 },
+    /// Building the shared template database failed.
+    Template(BoxError),
+    /// `CREATE DATABASE` failed.
+    CreateDatabase(DieselError),
+    /// Preparing the created database failed during `phase`, e.g.
+    /// `run migrations`. The database has been dropped again.
+    Setup {
+        dbname: String,
+        phase: &'static str,
+        source: BoxError,
+    },
+    /// The connection pool couldn't be built.
+    Pool(PoolError),
+    /// The async connection pool couldn't be built.
+    #[cfg(feature = "async")]
+    AsyncPool(diesel_async::pooled_connection::deadpool::BuildError),
+    /// The sqlx pool couldn't connect.
+    #[cfg(feature = "sqlx")]
+    SqlxPool(sqlx::Error),
+    /// The Postgres container couldn't be started.
+    #[cfg(feature = "testcontainers")]
+    Container(testcontainers_modules::testcontainers::TestcontainersError),
+    /// The embedded Postgres server couldn't be started.
+    #[cfg(feature = "embedded-pg")]
+    EmbeddedServer(std::io::Error),
+}
+
+impl fmt::Display for TestDbError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TestDbError::Config(message) => f.write_str(message),
+            TestDbError::Connect { url, source } => {
+                write!(f, "Error connecting to {}: {}", url, source)
+            }
+            TestDbError::UnsupportedVersion { actual, minimum } => write!(
+                f,
+                "The test server runs Postgres {}, older than the minimum supported version {}",
+                actual, minimum
+            ),
+            TestDbError::Template(e) => write!(f, "Failed to build the template database: {}", e),
+            TestDbError::CreateDatabase(e) => write!(f, "Failed to create test database: {}", e),
+            TestDbError::Setup {
+                dbname,
+                phase,
+                source,
+            } => write!(f, "Failed to {} in {}: {}", phase, dbname, source),
+            TestDbError::Pool(e) => write!(f, "Failed to create pool: {}", e),
+            #[cfg(feature = "async")]
+            TestDbError::AsyncPool(e) => write!(f, "Failed to create async pool: {}", e),
+            #[cfg(feature = "sqlx")]
+            TestDbError::SqlxPool(e) => write!(f, "Failed to create sqlx pool: {}", e),
+            #[cfg(feature = "testcontainers")]
+            TestDbError::Container(e) => write!(f, "Failed to start the Postgres container: {}", e),
+            #[cfg(feature = "embedded-pg")]
+            TestDbError::EmbeddedServer(e) => {
+                write!(f, "Failed to start the embedded Postgres server: {}", e)
+            }
+        }
+    }
+}
+
+impl Error for TestDbError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            TestDbError::Config(_) | TestDbError::UnsupportedVersion { .. } => None,
+            TestDbError::Connect { source, .. } => Some(source),
+            TestDbError::Template(e) | TestDbError::Setup { source: e, .. } => Some(&**e),
+            TestDbError::CreateDatabase(e) => Some(e),
+            TestDbError::Pool(e) => Some(e),
+            #[cfg(feature = "async")]
+            TestDbError::AsyncPool(e) => Some(e),
+            #[cfg(feature = "sqlx")]
+            TestDbError::SqlxPool(e) => Some(e),
+            #[cfg(feature = "testcontainers")]
+            TestDbError::Container(e) => Some(e),
+            #[cfg(feature = "embedded-pg")]
+            TestDbError::EmbeddedServer(e) => Some(e),
+        }
+    }
+}
@@ -0,0 +1,110 @@
+//! Structured lifecycle log for CI artifacts.
+//!
+//! When `TESTDB_EVENT_LOG` names a file, one JSON object per line is appended
+//! to it for every lifecycle event of every test database, e.g.
+//!
+//! ```text
+//! {"timestamp":"2022-12-08T03:11:40.123Z","event":"created","database":"test_0u2Kx1aB0003","pid":4242,"duration_ms":12}
+//! ```
+
+use std::{
+    env,
+    fs::{File, OpenOptions},
+    io::Write,
+    sync::{Mutex, OnceLock, PoisonError},
+    time::Duration,
+};
+
+use chrono::{SecondsFormat, Utc};
+use log::warn;
+use serde::Serialize;
+
+/// What happened to a test database.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LifecycleEvent {
+    Created,
+    Migrated,
+    Dropped,
+    /// The database was left behind, on purpose or because dropping failed.
+    Leaked,
+}
+
+#[derive(Serialize)]
+struct Record<'a> {
+    timestamp: String,
+    event: LifecycleEvent,
+    database: &'a str,
+    pid: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    duration_ms: Option<u128>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<&'a str>,
+}
+
+static LOG: OnceLock<Option<Mutex<File>>> = OnceLock::new();
+
+fn log_file() -> Option<&'static Mutex<File>> {
+    LOG.get_or_init(|| {
+        let path = env::var_os("TESTDB_EVENT_LOG")?;
+        match OpenOptions::new().create(true).append(true).open(&path) {
+            Ok(file) => Some(Mutex::new(file)),
+            Err(e) => {
+                warn!("Failed to open event log {:?}: {}", path, e);
+                None
+            }
+        }
+    })
+    .as_ref()
+}
+
+/// Append an event to the lifecycle log, if one is configured.
+pub(crate) fn record(
+    event: LifecycleEvent,
+    database: &str,
+    duration: Option<Duration>,
+    error: Option<&str>,
+) {
+    let Some(file) = log_file() else {
+        return;
+    };
+    let record = Record {
+        timestamp: Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+        event,
+        database,
+        pid: std::process::id(),
+        duration_ms: duration.map(|d| d.as_millis()),
+        error,
+    };
+    let mut line = serde_json::to_string(&record).expect("event records always serialize");
+    line.push('\n');
+    // a single write per line keeps concurrent writers from interleaving
+    if let Err(e) = file
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .write_all(line.as_bytes())
+    {
+        warn!("Failed to write event log: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn records_should_serialize_as_one_json_line() {
+        let record = Record {
+            timestamp: "2022-12-08T03:11:40.000Z".into(),
+            event: LifecycleEvent::Leaked,
+            database: "test_1",
+            pid: 42,
+            duration_ms: None,
+            error: Some("connection refused"),
+        };
+        assert_eq!(
+            serde_json::to_string(&record).unwrap(),
+            r#"{"timestamp":"2022-12-08T03:11:40.000Z","event":"leaked","database":"test_1","pid":42,"error":"connection refused"}"#
+        );
+    }
+}
@@ -0,0 +1,121 @@
+//! Fixtures loaded from files: SQL scripts seeding a baseline dataset, and
+//! binary contents too unwieldy to embed in them.
+//!
+//! Relative paths are resolved against the working directory, which `cargo
+//! test` sets to the package root.
+
+use std::{error::Error, fs, path::Path, sync::Arc};
+
+use diesel::{
+    connection::SimpleConnection,
+    dsl::sql,
+    select, sql_query,
+    sql_types::{Binary, Oid},
+    Connection, PgConnection, RunQueryDsl,
+};
+
+use crate::{diagnostics, sql::quote_ident, TestDb};
+
+/// A closure seeding the database during setup, see
+/// [`TestDbBuilder::with_seed`](crate::TestDbBuilder::with_seed).
+pub(crate) type Seed =
+    Arc<dyn Fn(&mut PgConnection) -> Result<(), Box<dyn Error + Send + Sync>> + Send + Sync>;
+
+/// Execute the SQL scripts at `paths` in order, all in one transaction so a
+/// failing script leaves no partial data behind.
+pub(crate) fn load_sql(
+    conn: &mut PgConnection,
+    paths: &[impl AsRef<Path>],
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let scripts = paths
+        .iter()
+        .map(|path| {
+            let path = path.as_ref();
+            fs::read_to_string(path)
+                .map(|script| (path, script))
+                .map_err(|e| format!("Failed to read fixture {}: {}", path.display(), e))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    conn.transaction(|conn| {
+        scripts.iter().try_for_each(|(path, script)| {
+            diagnostics::log(format_args!("loading fixture {}", path.display()));
+            conn.batch_execute(script)
+                .map_err(|e| format!("Failed to load fixture {}: {}", path.display(), e).into())
+        })
+    })
+}
+
+impl TestDb {
+    /// Execute the SQL scripts at `paths`, e.g. `INSERT`s of a baseline
+    /// dataset, in one transaction. To load them into every database of a
+    /// builder use [`TestDbBuilder::with_fixtures`](crate::TestDbBuilder::with_fixtures).
+    ///
+    /// ```no_run
+    /// # use diesel_database_tester::TestDb;
+    /// # let tdb = TestDb::builder().build();
+    /// tdb.load_fixtures(&["fixtures/users.sql", "fixtures/orders.sql"])
+    ///     .unwrap();
+    /// ```
+    pub fn load_fixtures(
+        &self,
+        paths: &[impl AsRef<Path>],
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        load_sql(&mut self.connect(), paths)
+    }
+
+    /// Insert a row into `table` whose bytea `column` holds the contents of
+    /// the file at `path`; the other columns get their defaults.
+    ///
+    /// ```no_run
+    /// # use diesel_database_tester::TestDb;
+    /// # let tdb = TestDb::builder().build();
+    /// tdb.fixture_blob("documents", "content", "fixtures/sample.pdf").unwrap();
+    /// ```
+    pub fn fixture_blob(
+        &self,
+        table: &str,
+        column: &str,
+        path: impl AsRef<Path>,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let bytes = read(path.as_ref())?;
+        let query = format!(
+            "INSERT INTO {} ({}) VALUES ($1)",
+            quote_ident(table),
+            quote_ident(column)
+        );
+        diagnostics::log(format_args!(
+            "executing: {} with {} bytes",
+            query,
+            bytes.len()
+        ));
+        sql_query(query)
+            .bind::<Binary, _>(bytes)
+            .execute(&mut self.connect())?;
+        Ok(())
+    }
+
+    /// Store the contents of the file at `path` as a large object and return
+    /// its oid, to be referenced from an `oid` column.
+    pub fn fixture_large_object(
+        &self,
+        path: impl AsRef<Path>,
+    ) -> Result<u32, Box<dyn Error + Send + Sync>> {
+        let bytes = read(path.as_ref())?;
+        let oid = select(
+            sql::<Oid>("lo_from_bytea(0, ")
+                .bind::<Binary, _>(bytes)
+                .sql(")"),
+        )
+        .get_result(&mut self.connect())?;
+        diagnostics::log(format_args!(
+            "stored {} as large object {}",
+            path.as_ref().display(),
+            oid
+        ));
+        Ok(oid)
+    }
+}
+
+fn read(path: &Path) -> Result<Vec<u8>, Box<dyn Error + Send + Sync>> {
+    fs::read(path).map_err(|e| format!("Failed to read fixture {}: {}", path.display(), e).into())
+}
@@ -0,0 +1,192 @@
+//! Branching the state of a test database into independent copies.
+
+use std::time::Instant;
+
+use diesel::{sql_query, sql_types::Text, PgConnection, QueryResult, QueryableByName, RunQueryDsl};
+use log::warn;
+
+use crate::{
+    app_role, connect_to,
+    connection::with_database,
+    diagnostics,
+    events::{self, LifecycleEvent},
+    sql, stale, throttle, DieselError, SetupStats, TestDb, TestDbError, CREATE_DATABASE_ATTEMPTS,
+};
+
+#[derive(QueryableByName)]
+struct Setting {
+    #[diesel(sql_type = Text)]
+    setting: String,
+}
+
+impl TestDb {
+    /// Clone the current state of the database, schema and rows, into a new
+    /// test database, so an expensively seeded state can be branched into
+    /// independent scenarios. The copy is dropped like any other `TestDb`.
+    ///
+    /// Postgres only copies databases nobody is connected to, so every open
+    /// connection to this one is terminated first: pools reconnect on their
+    /// next checkout, but connections held across the call are broken.
+    ///
+    /// ```no_run
+    /// # use diesel_database_tester::TestDb;
+    /// let seeded = TestDb::builder().build();
+    /// // ... expensive seeding ...
+    /// let approved = seeded.fork();
+    /// let rejected = seeded.fork();
+    /// ```
+    pub fn fork(&self) -> TestDb {
+        self.try_fork()
+            .unwrap_or_else(|e| panic!("Failed to fork {}: {}", self.dbname, e))
+    }
+
+    /// Like [`fork`](Self::fork), returning the error instead of panicking.
+    pub fn try_fork(&self) -> Result<TestDb, TestDbError> {
+        self.copy("fork")
+    }
+
+    /// A new test database copied from this one, `label` ending its name.
+    pub(crate) fn copy(&self, label: &str) -> Result<TestDb, TestDbError> {
+        let _permit = throttle::acquire();
+        let start = Instant::now();
+        let server_url = self.server_url();
+        let mut conn = connect_to(&server_url)?;
+        let dbname = self.namer.name(Some(label)).map_err(TestDbError::Config)?;
+        copy_database(&mut conn, &self.dbname, &dbname).map_err(TestDbError::CreateDatabase)?;
+        events::record(
+            LifecycleEvent::Created,
+            &dbname,
+            Some(start.elapsed()),
+            None,
+        );
+        let copy = TestDb {
+            host: self.host.clone(),
+            port: self.port,
+            user: self.user.clone(),
+            password: self.password.clone(),
+            sslmode: self.sslmode.clone(),
+            dbname,
+            config: self.config.clone(),
+            migrations: self.migrations.clone(),
+            pool_size: self.pool_size,
+            pool_timeout: self.pool_timeout,
+            pool_min_idle: self.pool_min_idle,
+            pool_idle_timeout: self.pool_idle_timeout,
+            // the role belongs to the source database
+            app_password: None,
+            app_role: None,
+            fixtures: self.fixtures.clone(),
+            seeds: self.seeds.clone(),
+            keep: false,
+            closed: false,
+            drop_timeout: self.drop_timeout,
+            replica: None,
+            namer: self.namer.clone(),
+            // a copy of the migrated and seeded source
+            stats: SetupStats {
+                create_database: start.elapsed(),
+                total: start.elapsed(),
+                ..SetupStats::default()
+            },
+            #[cfg(feature = "testcontainers")]
+            container: self.container.clone(),
+            #[cfg(feature = "embedded-pg")]
+            embedded: self.embedded.clone(),
+        };
+        if self.app_password.is_some() {
+            // dropped again along with the copy on failure
+            revoke_app_role(&server_url, &copy.dbname, &self.dbname)?;
+        }
+        Ok(copy)
+    }
+}
+
+/// Take back in `dbname`, a copy of the database of the app role `role`,
+/// everything granted to that role, so it can still be dropped with its own
+/// database while the copy lives on.
+pub(crate) fn revoke_app_role(
+    server_url: &str,
+    dbname: &str,
+    role: &str,
+) -> Result<(), TestDbError> {
+    let mut conn = connect_to(&with_database(server_url, dbname))?;
+    app_role::revoke_sql(role)
+        .iter()
+        .try_for_each(|sql| diagnostics::execute(&mut conn, sql).map(|_| ()))
+        .map_err(|e| TestDbError::Setup {
+            dbname: dbname.to_string(),
+            phase: "revoke the privileges of the source's app role",
+            source: e.into(),
+        })
+}
+
+/// Create `dbname` as a copy of `source` with the same `ALTER DATABASE ...
+/// SET` defaults, which templates don't carry over, after terminating the
+/// connections to `source`.
+pub(crate) fn copy_database(
+    conn: &mut PgConnection,
+    source: &str,
+    dbname: &str,
+) -> QueryResult<()> {
+    execute_disconnected(
+        conn,
+        source,
+        &sql::create_database(dbname, Some(source), None),
+    )?;
+    let settings: Vec<Setting> = sql_query(
+        "SELECT unnest(setconfig) AS setting FROM pg_db_role_setting \
+         WHERE setrole = 0 AND setdatabase = (SELECT oid FROM pg_database WHERE datname = $1)",
+    )
+    .bind::<Text, _>(source)
+    .load(conn)?;
+    diagnostics::execute(conn, &stale::mark_created(dbname))?;
+    for Setting { setting } in settings {
+        if let Some((name, value)) = setting.split_once('=') {
+            diagnostics::execute(conn, &sql::alter_database_set(dbname, name, value))?;
+        }
+    }
+    Ok(())
+}
+
+/// Replace `dbname` with a copy of `source`, made as `staging` first so that
+/// `dbname` is only dropped once its replacement is complete.
+pub(crate) fn replace_database(
+    conn: &mut PgConnection,
+    source: &str,
+    dbname: &str,
+    staging: &str,
+) -> QueryResult<()> {
+    let replaced = copy_database(conn, source, staging)
+        .and_then(|()| execute_disconnected(conn, dbname, &sql::drop_database(dbname)));
+    if let Err(e) = replaced {
+        // `dbname` is untouched, only the staging copy may be left over
+        let _ = diagnostics::execute(conn, &format!(r#"DROP DATABASE IF EXISTS "{}""#, staging));
+        return Err(e);
+    }
+    diagnostics::execute(conn, &sql::rename_database(staging, dbname)).map(|_| ())
+}
+
+/// Execute `statement`, which needs `dbname` to have no connections, after
+/// terminating them.
+pub(crate) fn execute_disconnected(
+    conn: &mut PgConnection,
+    dbname: &str,
+    statement: &str,
+) -> QueryResult<()> {
+    let mut attempt = 1;
+    loop {
+        diagnostics::execute(conn, &sql::terminate_connections(dbname))?;
+        match diagnostics::execute(conn, statement) {
+            Ok(_) => return Ok(()),
+            // a pool reconnected between terminating and executing
+            Err(DieselError::DatabaseError(_, info))
+                if attempt < CREATE_DATABASE_ATTEMPTS
+                    && info.message().contains("is being accessed by other users") =>
+            {
+                warn!("{} is still in use, retrying", dbname);
+                attempt += 1;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
@@ -0,0 +1,54 @@
+//! Statements and closures run on a new database right before and after its
+//! migrations, for what doesn't belong in the migration set: creating the
+//! roles migrations grant privileges to, refreshing materialized views, ...
+
+use std::error::Error;
+
+use diesel::{connection::SimpleConnection, PgConnection};
+
+use crate::diagnostics;
+
+/// Run on a new database around its migrations, see
+/// [`TestDbBuilder::before_migrations`](crate::TestDbBuilder::before_migrations).
+/// Implemented for SQL, which may hold several statements, and for closures
+/// getting the connection.
+pub trait MigrationHook: Send + Sync + 'static {
+    fn run(&self, conn: &mut PgConnection) -> Result<(), Box<dyn Error + Send + Sync>>;
+
+    /// The SQL run, shown by [`dry_run`](crate::TestDbBuilder::dry_run).
+    fn sql(&self) -> Option<String> {
+        None
+    }
+}
+
+impl MigrationHook for &'static str {
+    fn run(&self, conn: &mut PgConnection) -> Result<(), Box<dyn Error + Send + Sync>> {
+        diagnostics::log(format_args!("executing: {}", self));
+        Ok(conn.batch_execute(self)?)
+    }
+
+    fn sql(&self) -> Option<String> {
+        Some(self.to_string())
+    }
+}
+
+impl MigrationHook for String {
+    fn run(&self, conn: &mut PgConnection) -> Result<(), Box<dyn Error + Send + Sync>> {
+        diagnostics::log(format_args!("executing: {}", self));
+        Ok(conn.batch_execute(self)?)
+    }
+
+    fn sql(&self) -> Option<String> {
+        Some(self.clone())
+    }
+}
+
+impl<F, E> MigrationHook for F
+where
+    F: Fn(&mut PgConnection) -> Result<(), E> + Send + Sync + 'static,
+    E: Into<Box<dyn Error + Send + Sync>>,
+{
+    fn run(&self, conn: &mut PgConnection) -> Result<(), Box<dyn Error + Send + Sync>> {
+        self(conn).map_err(Into::into)
+    }
+}
@@ -0,0 +1,334 @@
+//! Column metadata read from the catalog, used to generate valid rows.
+
+use diesel::{
+    sql_query,
+    sql_types::{Array, Bool, Integer, Text},
+    PgConnection, QueryResult, QueryableByName, RunQueryDsl,
+};
+
+/// A column of a table as far as row generation cares.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Column {
+    pub name: String,
+    /// `pg_type.typname`, e.g. `int4` or `varchar`.
+    pub type_name: String,
+    pub typmod: i32,
+    pub not_null: bool,
+    /// Serial, identity and generated columns are left to the server.
+    pub generated: bool,
+    /// Labels of an enum type, empty for other types.
+    pub enum_labels: Vec<String>,
+    pub bounds: Bounds,
+}
+
+/// Value limits taken from the type modifier and simple check constraints.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct Bounds {
+    /// Lower bound and whether it is inclusive.
+    pub min: Option<(f64, bool)>,
+    /// Upper bound and whether it is inclusive.
+    pub max: Option<(f64, bool)>,
+    pub min_len: Option<usize>,
+    pub max_len: Option<usize>,
+}
+
+/// Type names rows can be generated for, besides enums.
+const SUPPORTED_TYPES: &[&str] = &[
+    "bool",
+    "int2",
+    "int4",
+    "int8",
+    "float4",
+    "float8",
+    "numeric",
+    "text",
+    "varchar",
+    "bpchar",
+    "date",
+    "timestamp",
+    "timestamptz",
+    "uuid",
+];
+/// Text columns without a length limit get at most this many characters.
+const DEFAULT_MAX_LEN: usize = 32;
+/// Range used for floats without check constraints.
+const DEFAULT_FLOAT_RANGE: f64 = 1e6;
+/// Generated dates and timestamps fall between 1970 and 2100.
+pub(crate) const MAX_EPOCH_SECS: i64 = 4_102_444_800;
+
+impl Bounds {
+    /// The integers within the bounds, clamped to `min..=max`.
+    pub fn int_range(&self, min: i64, max: i64) -> (i64, i64) {
+        let lo = match self.min {
+            Some((lo, true)) => lo.ceil(),
+            Some((lo, false)) => lo.floor() + 1.0,
+            None => min as f64,
+        };
+        let hi = match self.max {
+            Some((hi, true)) => hi.floor(),
+            Some((hi, false)) => hi.ceil() - 1.0,
+            None => max as f64,
+        };
+        ((lo as i64).max(min), (hi as i64).min(max))
+    }
+
+    /// A finite float range covering the bounds, exclusive ends included.
+    pub fn float_range(&self) -> (f64, f64) {
+        let lo = self.min.map(|(lo, _)| lo);
+        let hi = self.max.map(|(hi, _)| hi);
+        match (lo, hi) {
+            (Some(lo), Some(hi)) => (lo, hi),
+            (Some(lo), None) => (lo, lo + 2.0 * DEFAULT_FLOAT_RANGE),
+            (None, Some(hi)) => (hi - 2.0 * DEFAULT_FLOAT_RANGE, hi),
+            (None, None) => (-DEFAULT_FLOAT_RANGE, DEFAULT_FLOAT_RANGE),
+        }
+    }
+
+    /// The allowed string lengths.
+    pub fn len_range(&self) -> (usize, usize) {
+        let min = self.min_len.unwrap_or(0);
+        (min, self.max_len.unwrap_or(DEFAULT_MAX_LEN.max(min)))
+    }
+
+    /// The bounds of a `numeric` column in units of `10^-scale`.
+    pub fn scaled(&self, scale: u32) -> Bounds {
+        let unit = 10f64.powi(scale as i32);
+        Bounds {
+            min: self.min.map(|(lo, inclusive)| (lo * unit, inclusive)),
+            max: self.max.map(|(hi, inclusive)| (hi * unit, inclusive)),
+            ..Bounds::default()
+        }
+    }
+
+    pub fn contains(&self, value: f64) -> bool {
+        let above = match self.min {
+            Some((min, true)) => value >= min,
+            Some((min, false)) => value > min,
+            None => true,
+        };
+        let below = match self.max {
+            Some((max, true)) => value <= max,
+            Some((max, false)) => value < max,
+            None => true,
+        };
+        above && below
+    }
+}
+
+impl Column {
+    /// `(precision, scale)` of a `numeric(p, s)` column, `numeric(12, 2)` when
+    /// unconstrained.
+    pub fn numeric_precision(&self) -> (u32, u32) {
+        if self.typmod >= 4 {
+            let typmod = (self.typmod - 4) as u32;
+            (typmod >> 16, typmod & 0xffff)
+        } else {
+            (12, 2)
+        }
+    }
+
+    /// Largest absolute `numeric` value in units of `10^-scale`, kept within
+    /// the digits the column can store and within `i64`.
+    pub fn numeric_limit(&self) -> i64 {
+        let (precision, _) = self.numeric_precision();
+        10i64.pow(precision.min(18)) - 1
+    }
+
+    pub fn check_supported(&self) -> Result<(), String> {
+        if self.enum_labels.is_empty() && !SUPPORTED_TYPES.contains(&self.type_name.as_str()) {
+            return Err(format!(
+                "Can't generate values of type {} for column {}",
+                self.type_name, self.name
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[derive(QueryableByName)]
+struct RawColumn {
+    #[diesel(sql_type = Text)]
+    name: String,
+    #[diesel(sql_type = Text)]
+    type_name: String,
+    #[diesel(sql_type = Integer)]
+    typmod: i32,
+    #[diesel(sql_type = Bool)]
+    not_null: bool,
+    #[diesel(sql_type = Bool)]
+    generated: bool,
+    #[diesel(sql_type = Array<Text>)]
+    enum_labels: Vec<String>,
+}
+
+#[derive(QueryableByName)]
+struct CheckConstraint {
+    #[diesel(sql_type = Text)]
+    definition: String,
+}
+
+/// The columns of `table` in declaration order, with their bounds.
+pub(crate) fn columns(conn: &mut PgConnection, table: &str) -> QueryResult<Vec<Column>> {
+    let raw: Vec<RawColumn> = sql_query(
+        "SELECT a.attname::text AS name, t.typname::text AS type_name, a.atttypmod AS typmod, \
+         a.attnotnull AS not_null, \
+         (a.attidentity <> '' OR a.attgenerated <> '' \
+          OR COALESCE(pg_get_expr(d.adbin, d.adrelid) LIKE 'nextval(%', false)) AS generated, \
+         ARRAY(SELECT e.enumlabel::text FROM pg_enum e WHERE e.enumtypid = a.atttypid \
+               ORDER BY e.enumsortorder) AS enum_labels \
+         FROM pg_attribute a \
+         JOIN pg_type t ON t.oid = a.atttypid \
+         LEFT JOIN pg_attrdef d ON d.adrelid = a.attrelid AND d.adnum = a.attnum \
+         WHERE a.attrelid = $1::regclass AND a.attnum > 0 AND NOT a.attisdropped \
+         ORDER BY a.attnum",
+    )
+    .bind::<Text, _>(table)
+    .load(conn)?;
+    let checks: Vec<CheckConstraint> = sql_query(
+        "SELECT pg_get_constraintdef(oid) AS definition FROM pg_constraint \
+         WHERE conrelid = $1::regclass AND contype = 'c'",
+    )
+    .bind::<Text, _>(table)
+    .load(conn)?;
+
+    Ok(raw
+        .into_iter()
+        .map(|raw| {
+            let mut bounds = Bounds {
+                max_len: matches!(raw.type_name.as_str(), "varchar" | "bpchar")
+                    .then(|| raw.typmod - 4)
+                    .filter(|len| *len > 0)
+                    .map(|len| len as usize),
+                ..Bounds::default()
+            };
+            for check in &checks {
+                apply_check(&mut bounds, &raw.name, &check.definition);
+            }
+            Column {
+                name: raw.name,
+                type_name: raw.type_name,
+                typmod: raw.typmod,
+                not_null: raw.not_null,
+                generated: raw.generated,
+                enum_labels: raw.enum_labels,
+                bounds,
+            }
+        })
+        .collect())
+}
+
+/// Narrow `bounds` with the parts of a check constraint that compare
+/// `column`, or its length, against a number. Anything more involved is
+/// ignored, e.g. `CHECK (price >= 0 AND price < 1000)` is understood but
+/// `CHECK (starts_at < ends_at)` isn't.
+fn apply_check(bounds: &mut Bounds, column: &str, definition: &str) {
+    let Some(expr) = definition
+        .strip_prefix("CHECK ")
+        .map(|expr| expr.trim_end_matches(" NOT VALID"))
+    else {
+        return;
+    };
+    if expr.contains(" OR ") {
+        return;
+    }
+    for part in expr.split(" AND ") {
+        // parentheses around each comparison are unbalanced once split
+        let part = part.trim().trim_start_matches('(').trim_end_matches(')');
+        let Some((lhs, op, rhs)) = [">=", "<=", ">", "<"].iter().find_map(|op| {
+            part.split_once(&format!(" {} ", op))
+                .map(|(lhs, rhs)| (lhs, *op, rhs))
+        }) else {
+            continue;
+        };
+        // negative constants come out quoted, `'-5'::integer`
+        let Ok(value) = strip_casts(rhs).trim_matches('\'').parse::<f64>() else {
+            continue;
+        };
+        let inclusive = op.len() == 2;
+        if unquote(strip_casts(lhs)) == column {
+            if op.starts_with('>') {
+                bounds.min = Some((value, inclusive));
+            } else {
+                bounds.max = Some((value, inclusive));
+            }
+        } else if let Some(arg) = ["length(", "char_length(", "character_length("]
+            .iter()
+            .find_map(|f| lhs.trim().strip_prefix(f))
+        {
+            if unquote(strip_casts(arg.trim_end_matches(')'))) != column {
+                continue;
+            }
+            match op {
+                ">=" => bounds.min_len = Some(value as usize),
+                ">" => bounds.min_len = Some(value as usize + 1),
+                "<=" => bounds.max_len = Some(value as usize),
+                _ => bounds.max_len = Some((value as usize).saturating_sub(1)),
+            }
+        }
+    }
+}
+
+/// `((x))` -> `x`
+fn strip_parens(mut expr: &str) -> &str {
+    expr = expr.trim();
+    while let Some(inner) = expr.strip_prefix('(').and_then(|e| e.strip_suffix(')')) {
+        expr = inner.trim();
+    }
+    expr
+}
+
+/// `(0)::numeric` -> `0`, `(name)::text` -> `name`
+fn strip_casts(expr: &str) -> &str {
+    let expr = strip_parens(expr);
+    let expr = match expr.find("::") {
+        Some(i) if !expr[i..].contains(')') => &expr[..i],
+        _ => expr,
+    };
+    strip_parens(expr)
+}
+
+fn unquote(ident: &str) -> String {
+    match ident.strip_prefix('"').and_then(|i| i.strip_suffix('"')) {
+        Some(quoted) => quoted.replace(r#""""#, r#"""#),
+        None => ident.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn simple_checks_should_narrow_bounds() {
+        let mut bounds = Bounds::default();
+        apply_check(
+            &mut bounds,
+            "price",
+            "CHECK (((price >= (0)::numeric) AND (price < (1000)::numeric)))",
+        );
+        assert_eq!(bounds.min, Some((0.0, true)));
+        assert_eq!(bounds.max, Some((1000.0, false)));
+        assert!(bounds.contains(999.5));
+        assert!(!bounds.contains(1000.0));
+
+        let mut bounds = Bounds::default();
+        apply_check(&mut bounds, "name", "CHECK ((length((name)::text) > 0))");
+        apply_check(&mut bounds, "name", "CHECK ((starts_at < ends_at))");
+        apply_check(
+            &mut bounds,
+            "name",
+            "CHECK (((qty > 0) OR (name = 'x'::text)))",
+        );
+        assert_eq!(
+            bounds,
+            Bounds {
+                min_len: Some(1),
+                ..Bounds::default()
+            }
+        );
+
+        let mut bounds = Bounds::default();
+        apply_check(&mut bounds, "qty", "CHECK ((qty > '-5'::integer))");
+        assert_eq!(bounds.min, Some((-5.0, false)));
+    }
+}
@@ -0,0 +1,90 @@
+//! Detection of tests leaking rows into a database shared between them.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Mutex, OnceLock},
+};
+
+use diesel::{
+    dsl::sql, select, sql_query, sql_types::Text, PgConnection, QueryResult, QueryableByName,
+    RunQueryDsl,
+};
+
+use crate::TestDb;
+
+/// Table checksums of each database as of the last check, by database name.
+static BASELINES: OnceLock<Mutex<HashMap<String, BTreeMap<String, String>>>> = OnceLock::new();
+
+#[derive(QueryableByName)]
+struct Table {
+    #[diesel(sql_type = Text)]
+    name: String,
+}
+
+/// An md5 of the contents of every user table, by qualified table name.
+pub(crate) fn table_checksums(conn: &mut PgConnection) -> QueryResult<BTreeMap<String, String>> {
+    let tables: Vec<Table> = sql_query(
+        "SELECT quote_ident(schemaname) || '.' || quote_ident(tablename) AS name FROM pg_tables \
+         WHERE schemaname NOT IN ('pg_catalog', 'information_schema') \
+         AND schemaname NOT LIKE 'pg_temp%' AND tablename <> '__diesel_schema_migrations' \
+         ORDER BY 1",
+    )
+    .load(conn)?;
+    tables
+        .into_iter()
+        .map(|table| {
+            let checksum = select(sql::<Text>(&format!(
+                "(SELECT md5(COALESCE(string_agg(t::text, E'\\n' ORDER BY t::text), '')) FROM {} t)",
+                table.name
+            )))
+            .get_result(conn)?;
+            Ok((table.name, checksum))
+        })
+        .collect()
+}
+
+impl TestDb {
+    /// Check that no table changed since the previous call, for databases
+    /// shared between tests that are each supposed to clean up after
+    /// themselves. Call it at the end of every such test with the test's
+    /// name: the first call records the baseline, later ones panic naming
+    /// `test` and the tables whose contents it left changed. The baseline
+    /// then moves on, so the next test isn't blamed for the same leak.
+    ///
+    /// ```no_run
+    /// # use diesel_database_tester::TestDb;
+    /// # let shared = TestDb::builder().build();
+    /// shared.assert_isolated("setup");
+    /// // ... test body, cleaning up after itself ...
+    /// shared.assert_isolated("todos::create");
+    /// ```
+    pub fn assert_isolated(&self, test: &str) {
+        let current = table_checksums(&mut self.connect())
+            .unwrap_or_else(|e| panic!("Failed to checksum tables of {}: {}", self.dbname, e));
+        let previous = BASELINES
+            .get_or_init(Default::default)
+            .lock()
+            .unwrap()
+            .insert(self.dbname.clone(), current.clone());
+        let Some(previous) = previous else {
+            return;
+        };
+        let leaked: Vec<_> = current
+            .keys()
+            .chain(
+                previous
+                    .keys()
+                    .filter(|table| !current.contains_key(*table)),
+            )
+            .filter(|table| current.get(*table) != previous.get(*table))
+            .cloned()
+            .collect();
+        assert!(
+            leaked.is_empty(),
+            "{} leaked data into the shared database {}, changed tables: {}",
+            test,
+            self.dbname,
+            leaked.join(", ")
+        );
+    }
+}
@@ -0,0 +1,68 @@
+//! Keeping test databases after the test for post-mortem debugging, with
+//! [`TestDb::keep_on_drop`](crate::TestDb::keep_on_drop) or `TESTDB_KEEP`.
+//!
+//! `TESTDB_KEEP=1` keeps the databases of failing tests, i.e. those dropped
+//! while panicking, and `TESTDB_KEEP=always` keeps every database.
+
+use std::{env, sync::OnceLock, thread};
+
+use crate::connection::redact_url;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum KeepMode {
+    Never,
+    OnFailure,
+    Always,
+}
+
+static MODE: OnceLock<KeepMode> = OnceLock::new();
+
+fn parse(value: &str) -> KeepMode {
+    match value {
+        "" | "0" | "false" => KeepMode::Never,
+        "always" | "all" => KeepMode::Always,
+        _ => KeepMode::OnFailure,
+    }
+}
+
+fn mode() -> KeepMode {
+    *MODE.get_or_init(|| {
+        env::var("TESTDB_KEEP")
+            .map(|v| parse(&v))
+            .unwrap_or(KeepMode::Never)
+    })
+}
+
+/// Whether a database being dropped should be kept, `requested` being set by
+/// [`TestDb::keep_on_drop`](crate::TestDb::keep_on_drop).
+pub(crate) fn should_keep(requested: bool) -> bool {
+    requested
+        || match mode() {
+            KeepMode::Never => false,
+            KeepMode::OnFailure => thread::panicking(),
+            KeepMode::Always => true,
+        }
+}
+
+/// Tell the user where to find a kept database.
+pub(crate) fn announce(dbname: &str, url: &str) {
+    eprintln!(
+        "[testdb] kept test database {}, inspect it with: psql '{}'",
+        dbname,
+        redact_url(url)
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keep_mode_should_parse_env_values() {
+        assert_eq!(parse(""), KeepMode::Never);
+        assert_eq!(parse("0"), KeepMode::Never);
+        assert_eq!(parse("false"), KeepMode::Never);
+        assert_eq!(parse("1"), KeepMode::OnFailure);
+        assert_eq!(parse("always"), KeepMode::Always);
+    }
+}
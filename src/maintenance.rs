@@ -0,0 +1,120 @@
+//! Planner statistics and other maintenance on the test database.
+
+use diesel::QueryResult;
+
+use crate::{diagnostics, sql::quote_ident, TestDb};
+
+/// Kinds of extended statistics, see `CREATE STATISTICS`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatisticsKind {
+    /// Number of distinct values of the column group.
+    NDistinct,
+    /// Functional dependencies between the columns.
+    Dependencies,
+    /// Most common value combinations.
+    Mcv,
+}
+
+impl StatisticsKind {
+    fn as_sql(&self) -> &'static str {
+        match self {
+            StatisticsKind::NDistinct => "ndistinct",
+            StatisticsKind::Dependencies => "dependencies",
+            StatisticsKind::Mcv => "mcv",
+        }
+    }
+}
+
+/// Turns autovacuum off for every table in the database, including their
+/// TOAST tables.
+pub(crate) const DISABLE_AUTOVACUUM: &str = "DO $$ DECLARE t regclass; BEGIN \
+     FOR t IN SELECT c.oid::regclass FROM pg_class c \
+     JOIN pg_namespace n ON n.oid = c.relnamespace \
+     WHERE c.relkind IN ('r', 'm') AND n.nspname <> 'information_schema' \
+     AND n.nspname NOT LIKE 'pg\\_%' LOOP \
+     EXECUTE format('ALTER TABLE %s SET (autovacuum_enabled = off, \
+     toast.autovacuum_enabled = off)', t); \
+     END LOOP; END $$";
+
+/// Take `autovacuum` out of `settings`, as it can't be set per database: its
+/// value, if given, and the remaining settings.
+pub(crate) fn split_autovacuum(
+    settings: &[(String, String)],
+) -> (Option<bool>, Vec<(String, String)>) {
+    let mut autovacuum = None;
+    let mut rest = Vec::new();
+    for (name, value) in settings {
+        if name.eq_ignore_ascii_case("autovacuum") {
+            autovacuum = Some(!matches!(
+                value.to_ascii_lowercase().as_str(),
+                "off" | "false" | "0" | "no"
+            ));
+        } else {
+            rest.push((name.clone(), value.clone()));
+        }
+    }
+    (autovacuum, rest)
+}
+
+impl TestDb {
+    /// Run `ANALYZE` on the whole database, so planner-dependent tests (e.g.
+    /// `EXPLAIN` assertions) see realistic statistics instead of the default
+    /// empty-table estimates.
+    pub fn analyze(&self) -> QueryResult<()> {
+        diagnostics::execute(&mut self.connect(), "ANALYZE")?;
+        Ok(())
+    }
+
+    /// Create extended statistics named `name` on a group of columns of
+    /// `table` and analyze the table so they are populated, for tests relying
+    /// on multi-column correlation estimates. An empty `kinds` builds every
+    /// kind the server supports.
+    pub fn create_statistics(
+        &self,
+        name: &str,
+        table: &str,
+        columns: &[&str],
+        kinds: &[StatisticsKind],
+    ) -> QueryResult<()> {
+        let kinds = if kinds.is_empty() {
+            String::new()
+        } else {
+            let kinds = kinds.iter().map(|k| k.as_sql()).collect::<Vec<_>>();
+            format!(" ({})", kinds.join(", "))
+        };
+        let columns = columns
+            .iter()
+            .map(|c| quote_ident(c))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let mut conn = self.connect();
+        diagnostics::execute(
+            &mut conn,
+            &format!(
+                "CREATE STATISTICS {}{} ON {} FROM {}",
+                quote_ident(name),
+                kinds,
+                columns,
+                quote_ident(table)
+            ),
+        )?;
+        diagnostics::execute(&mut conn, &format!("ANALYZE {}", quote_ident(table)))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn autovacuum_should_be_split_from_the_settings() {
+        let settings = [("jit", "off"), ("autovacuum", "OFF")]
+            .map(|(name, value)| (name.to_string(), value.to_string()));
+        assert_eq!(
+            split_autovacuum(&settings),
+            (Some(false), vec![("jit".to_string(), "off".to_string())])
+        );
+        assert_eq!(split_autovacuum(&[]), (None, vec![]));
+    }
+}
@@ -0,0 +1,31 @@
+//! Metrics for long-running harnesses, reported through the `metrics` facade
+//! when the `metrics` feature is enabled. Install any exporter (e.g.
+//! `metrics-exporter-prometheus`) to scrape them:
+//!
+//! - `testdb_databases_created_total` (counter)
+//! - `testdb_databases_active` (gauge)
+//! - `testdb_setup_duration_seconds` (histogram)
+//! - `testdb_drop_failures_total` (counter)
+
+use std::time::Duration;
+
+pub(crate) fn database_created(setup: Duration) {
+    #[cfg(feature = "metrics")]
+    {
+        ::metrics::counter!("testdb_databases_created_total").increment(1);
+        ::metrics::gauge!("testdb_databases_active").increment(1.0);
+        ::metrics::histogram!("testdb_setup_duration_seconds").record(setup.as_secs_f64());
+    }
+    #[cfg(not(feature = "metrics"))]
+    let _ = setup;
+}
+
+pub(crate) fn database_dropped() {
+    #[cfg(feature = "metrics")]
+    ::metrics::gauge!("testdb_databases_active").decrement(1.0);
+}
+
+pub(crate) fn drop_failed() {
+    #[cfg(feature = "metrics")]
+    ::metrics::counter!("testdb_drop_failures_total").increment(1);
+}
@@ -0,0 +1,165 @@
+//! The migrations applied to test databases.
+
+use std::{any::Any, collections::HashSet, path::Path, sync::Arc};
+
+use diesel::{
+    connection::{BoxableConnection, SimpleConnection},
+    migration::{self, Migration, MigrationSource},
+    pg::Pg,
+    QueryResult,
+};
+use diesel_migrations::{FileBasedMigrations, MigrationHarness};
+
+use crate::{TestDbError, MIGRATIONS};
+
+/// The migration sources chosen with
+/// [`TestDbBuilder::migrations`](crate::TestDbBuilder::migrations) and
+/// [`add_migrations`](crate::TestDbBuilder::add_migrations), cheap to clone
+/// into every place that runs or fingerprints migrations.
+#[derive(Clone)]
+pub(crate) struct MigrationSet {
+    sources: Vec<Source>,
+    /// Still the migrations of this crate, replaced rather than extended by
+    /// another source.
+    default: bool,
+}
+
+#[derive(Clone)]
+struct Source {
+    migrations: Arc<dyn MigrationSource<Pg> + Send + Sync>,
+}
+
+impl MigrationSet {
+    pub fn new(source: impl MigrationSource<Pg> + Send + Sync + 'static) -> Self {
+        Self {
+            sources: vec![Source {
+                migrations: Arc::new(source),
+            }],
+            default: false,
+        }
+    }
+
+    /// The migrations in `dir`, a diesel migrations directory.
+    pub fn try_from_dir(dir: &Path) -> Result<Self, TestDbError> {
+        let migrations = FileBasedMigrations::from_path(dir).map_err(|e| {
+            TestDbError::Config(format!(
+                "Failed to read migrations from {}: {}",
+                dir.display(),
+                e
+            ))
+        })?;
+        Ok(Self::new(migrations))
+    }
+
+    /// These migrations followed by `other`'s, or only `other`'s if these are
+    /// still the default ones.
+    pub fn then(mut self, other: MigrationSet) -> Self {
+        if self.default {
+            return other;
+        }
+        self.sources.extend(other.sources);
+        self
+    }
+
+    /// The migrations not applied yet on `conn`, source by source in the
+    /// order the sources were added, each source's sorted by version.
+    pub fn pending(
+        &self,
+        conn: &mut impl MigrationHarness<Pg>,
+    ) -> migration::Result<Vec<Box<dyn Migration<Pg>>>> {
+        let mut seen = HashSet::new();
+        let mut pending = Vec::new();
+        for source in &self.sources {
+            for migration in conn.pending_migrations(source)? {
+                let version = migration.name().version().as_owned();
+                if !seen.insert(version.to_string()) {
+                    return Err(format!(
+                        "Migration version {} is in more than one migration source",
+                        version
+                    )
+                    .into());
+                }
+                pending.push(migration);
+            }
+        }
+        Ok(pending)
+    }
+
+    /// Names of all migrations in order, empty when they can't be listed.
+    pub fn names(&self) -> Vec<String> {
+        self.migrations()
+            .map(|migrations| {
+                migrations
+                    .iter()
+                    .map(|migration| migration.name().to_string())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// What identifies the migrations for the schema cache and templates:
+    /// their names and the SQL they run up and down, so editing an existing
+    /// migration also invalidates them, embedded or not.
+    pub fn fingerprint(&self) -> Vec<String> {
+        let migrations = match self.migrations() {
+            Ok(migrations) => migrations,
+            Err(_) => return Vec::new(),
+        };
+        migrations
+            .iter()
+            .map(|migration| {
+                let mut recorder = SqlRecorder::default();
+                // a migration not going through `batch_execute` is only known by name
+                let _ = migration.run(&mut recorder);
+                let _ = migration.revert(&mut recorder);
+                format!("{}\0{}", migration.name(), recorder.0.join("\0"))
+            })
+            .collect()
+    }
+}
+
+/// A stand-in connection collecting the SQL a migration runs on it.
+#[derive(Default)]
+struct SqlRecorder(Vec<String>);
+
+impl SimpleConnection for SqlRecorder {
+    fn batch_execute(&mut self, query: &str) -> QueryResult<()> {
+        self.0.push(query.to_string());
+        Ok(())
+    }
+}
+
+impl BoxableConnection<Pg> for SqlRecorder {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+impl Default for MigrationSet {
+    fn default() -> Self {
+        Self {
+            default: true,
+            ..Self::new(MIGRATIONS)
+        }
+    }
+}
+
+impl MigrationSource<Pg> for MigrationSet {
+    fn migrations(&self) -> migration::Result<Vec<Box<dyn Migration<Pg>>>> {
+        let mut migrations = Vec::new();
+        for source in &self.sources {
+            migrations.extend(source.migrations()?);
+        }
+        Ok(migrations)
+    }
+}
+
+impl MigrationSource<Pg> for &Source {
+    fn migrations(&self) -> migration::Result<Vec<Box<dyn Migration<Pg>>>> {
+        self.migrations.migrations()
+    }
+}
@@ -0,0 +1,231 @@
+//! Strategies used to name the databases created for each test.
+
+use std::{
+    env,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    },
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use uuid::Uuid;
+
+/// Prefix shared by every generated test database.
+pub(crate) const DEFAULT_PREFIX: &str = "test_";
+/// Postgres truncates identifiers longer than this many bytes.
+pub(crate) const MAX_IDENTIFIER_LEN: usize = 63;
+
+// ascii-ordered so that names sort the same way as the values they encode
+const BASE62: &[u8; 62] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+const TIMESTAMP_WIDTH: usize = 8;
+const COUNTER_WIDTH: usize = 4;
+
+static COUNTER: AtomicU32 = AtomicU32::new(0);
+
+/// How the unique part of a test database name is generated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DbNaming {
+    /// A full v4 uuid, e.g. `test_67e55044-10b1-426f-9247-bb680e5fe0c8`.
+    #[default]
+    Uuid,
+    /// A base62 millisecond timestamp followed by a per-process counter, e.g.
+    /// `test_0u2Kx1aB0003`. Short enough to append a test name to, and sorts
+    /// chronologically in `\l` output.
+    Short,
+}
+
+impl DbNaming {
+    /// Generate a fresh, unique identifier.
+    pub fn generate(&self) -> String {
+        match self {
+            DbNaming::Uuid => Uuid::new_v4().to_string(),
+            DbNaming::Short => {
+                let millis = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map(|d| d.as_millis() as u64)
+                    .unwrap_or_default();
+                let counter = COUNTER.fetch_add(1, Ordering::Relaxed) as u64;
+                format!(
+                    "{}{}",
+                    base62(millis, TIMESTAMP_WIDTH),
+                    base62(counter, COUNTER_WIDTH)
+                )
+            }
+        }
+    }
+}
+
+/// What a custom database name is built from, see
+/// [`TestDbBuilder::name_with`](crate::TestDbBuilder::name_with).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NameParts<'a> {
+    /// The [prefix](crate::TestDbBuilder::prefix), `test_` by default.
+    pub prefix: &'a str,
+    /// A fresh identifier generated with the builder's [`DbNaming`].
+    pub unique: &'a str,
+    /// The sanitized [label](crate::TestDbBuilder::named), if any.
+    pub label: Option<&'a str>,
+}
+
+/// A callback naming test databases.
+pub(crate) type NameFn = Arc<dyn Fn(&NameParts) -> String + Send + Sync>;
+
+/// How a builder names databases, kept by its `TestDb` to name the copies
+/// made of it the same way.
+#[derive(Clone)]
+pub(crate) struct Namer {
+    pub prefix: String,
+    pub naming: DbNaming,
+    pub name_with: Option<NameFn>,
+}
+
+impl Namer {
+    /// A fresh name ending in `label`, checked with [`check`].
+    pub fn name(&self, label: Option<&str>) -> Result<String, String> {
+        let name = match &self.name_with {
+            Some(name_with) => {
+                let label = label.map(sanitize);
+                name_with(&NameParts {
+                    prefix: &self.prefix,
+                    unique: &self.naming.generate(),
+                    label: label.as_deref().filter(|label| !label.is_empty()),
+                })
+            }
+            None => database_name(self.naming, &self.prefix, label),
+        };
+        check(&name)?;
+        Ok(name)
+    }
+}
+
+/// Whether `prefix` can start a database name.
+pub(crate) fn is_valid_prefix(prefix: &str) -> bool {
+    !prefix.is_empty()
+        && prefix
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// The prefix set with `TESTDB_PREFIX`, e.g. `ci_1234_` to tell which CI job
+/// leaked a database.
+pub(crate) fn prefix_from_env() -> Result<Option<String>, String> {
+    match env::var("TESTDB_PREFIX") {
+        Ok(prefix) if prefix.is_empty() => Ok(None),
+        Ok(prefix) if is_valid_prefix(&prefix) => Ok(Some(prefix)),
+        Ok(prefix) => Err(format!(
+            "TESTDB_PREFIX {:?} must only contain ASCII letters, digits and underscores",
+            prefix
+        )),
+        Err(_) => Ok(None),
+    }
+}
+
+/// Check that `name` is usable as is: Postgres would silently truncate a
+/// longer one, and the statements this crate runs don't escape quotes.
+pub(crate) fn check(name: &str) -> Result<(), String> {
+    if name.is_empty() {
+        Err("the database name is empty".into())
+    } else if name.len() > MAX_IDENTIFIER_LEN {
+        Err(format!(
+            "database name {} is longer than the {} bytes Postgres allows",
+            name, MAX_IDENTIFIER_LEN
+        ))
+    } else if !name
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    {
+        Err(format!(
+            "database name {} must only contain ASCII letters, digits, `_` and `-`",
+            name
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+/// Build a full database name from a prefix, a fresh identifier and an
+/// optional label, e.g. `test_0u2Kx1aB0003_users_create_flow`.
+pub(crate) fn database_name(naming: DbNaming, prefix: &str, label: Option<&str>) -> String {
+    let name = format!("{}{}", prefix, naming.generate());
+    match label.map(sanitize).filter(|label| !label.is_empty()) {
+        Some(label) => {
+            let room = MAX_IDENTIFIER_LEN.saturating_sub(name.len() + 1);
+            // keep the tail, the innermost part of a test path is the most specific
+            let label = label[label.len().saturating_sub(room)..].trim_start_matches('_');
+            if label.is_empty() {
+                name
+            } else {
+                format!("{}_{}", name, label)
+            }
+        }
+        None => name,
+    }
+}
+
+/// Turn a test path like `users::create_flow` into `users_create_flow`.
+pub(crate) fn sanitize(label: &str) -> String {
+    let mut sanitized = String::with_capacity(label.len());
+    for c in label.chars() {
+        if c.is_ascii_alphanumeric() {
+            sanitized.push(c.to_ascii_lowercase());
+        } else if !sanitized.ends_with('_') {
+            sanitized.push('_');
+        }
+    }
+    sanitized.trim_matches('_').to_string()
+}
+
+/// Encode `value` as a fixed-width base62 string, keeping the lowest digits
+/// when it doesn't fit.
+fn base62(mut value: u64, width: usize) -> String {
+    let mut digits = vec![b'0'; width];
+    for digit in digits.iter_mut().rev() {
+        *digit = BASE62[(value % 62) as usize];
+        value /= 62;
+    }
+    String::from_utf8(digits).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn short_names_should_be_compact_and_sorted() {
+        let first = DbNaming::Short.generate();
+        let second = DbNaming::Short.generate();
+        assert_eq!(first.len(), TIMESTAMP_WIDTH + COUNTER_WIDTH);
+        assert!(first < second);
+        assert_eq!(base62(61, 2), "0z");
+        assert_eq!(base62(62, 2), "10");
+    }
+
+    #[test]
+    fn labels_should_be_sanitized_and_fit_identifier_limit() {
+        assert_eq!(sanitize("users::create_flow"), "users_create_flow");
+        assert_eq!(sanitize("  Weird--Name!! "), "weird_name");
+
+        let name = database_name(DbNaming::Short, DEFAULT_PREFIX, Some("users::create_flow"));
+        assert!(name.starts_with("test_"));
+        assert!(name.ends_with("_users_create_flow"));
+
+        let name = database_name(
+            DbNaming::Uuid,
+            DEFAULT_PREFIX,
+            Some(&"very_long_module::".repeat(10)),
+        );
+        assert!(name.len() <= MAX_IDENTIFIER_LEN);
+        assert!(name.ends_with("_very_long_module"));
+    }
+
+    #[test]
+    fn names_should_be_checked_against_identifier_rules() {
+        assert!(check("test_0u2Kx1aB0003").is_ok());
+        assert!(check(&format!("{}_{}", "ci".repeat(20), Uuid::new_v4())).is_err());
+        assert!(check("").is_err());
+        assert!(check(r#"test_"quoted""#).is_err());
+        assert!(is_valid_prefix("ci_1234_orders_"));
+        assert!(!is_valid_prefix("ci-1234"));
+    }
+}
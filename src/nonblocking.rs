@@ -0,0 +1,100 @@
+//! Creating and dropping test databases from async code.
+//!
+//! Setup and teardown are plain blocking Diesel calls. The async variants run
+//! them on a dedicated thread named like the calling one, so the test name is
+//! still known, and only await the result, leaving the executor free and
+//! working with any runtime.
+
+use std::{
+    error::Error,
+    panic::{self, AssertUnwindSafe},
+    thread,
+};
+
+use log::info;
+use tokio::sync::oneshot;
+
+use crate::{drop_database, keep, report, TestDb, TestDbBuilder, TestDbError};
+
+/// Run `f` on its own thread and wait for it without blocking the executor,
+/// resuming its panic if it panics.
+async fn unblock<T: Send + 'static>(f: impl FnOnce() -> T + Send + 'static) -> T {
+    let (sender, receiver) = oneshot::channel();
+    let mut builder = thread::Builder::new();
+    if let Some(name) = thread::current().name() {
+        builder = builder.name(name.to_string());
+    }
+    builder
+        .spawn(move || {
+            let _ = sender.send(panic::catch_unwind(AssertUnwindSafe(f)));
+        })
+        .expect("Failed to spawn a test database thread");
+    match receiver.await.expect("The test database thread vanished") {
+        Ok(value) => value,
+        Err(panic) => panic::resume_unwind(panic),
+    }
+}
+
+impl TestDbBuilder {
+    /// Like [`build`](Self::build), without blocking the executor of an
+    /// async test.
+    ///
+    /// ```no_run
+    /// # async fn example() {
+    /// use diesel_database_tester::TestDb;
+    ///
+    /// let tdb = TestDb::builder().build_async().await;
+    /// // ...
+    /// tdb.close().await.unwrap();
+    /// # }
+    /// ```
+    pub async fn build_async(self) -> TestDb {
+        self.try_build_async()
+            .await
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Like [`try_build`](Self::try_build), without blocking the executor of
+    /// an async test.
+    pub async fn try_build_async(self) -> Result<TestDb, TestDbError> {
+        unblock(move || self.try_build()).await
+    }
+}
+
+impl TestDb {
+    /// Like [`new`](Self::new), without blocking the executor of an async
+    /// test.
+    pub async fn new_async(
+        host: impl Into<String>,
+        port: u16,
+        user: impl Into<String>,
+        password: impl Into<String>,
+        migration_path: &str,
+    ) -> Self {
+        TestDbBuilder::new()
+            .host(host)
+            .port(port)
+            .user(user)
+            .password(password)
+            .migrations_dir(migration_path)
+            .build_async()
+            .await
+    }
+
+    /// Drop the database now, without blocking the executor, and report
+    /// whether that worked. Dropping a `TestDb` instead does the same in the
+    /// background, only logging failures.
+    pub async fn close(mut self) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+        if keep::should_keep(self.keep) {
+            // announced by `Drop`
+            return Ok(());
+        }
+        let server_url = self.server_url();
+        let dbname = self.dbname.clone();
+        let test = report::current_test();
+        let timeout = self.drop_timeout;
+        self.closed = true;
+        info!("Closing test database {}", dbname);
+        unblock(move || drop_database(&server_url, &dbname, test.as_deref(), timeout)).await
+    }
+}
@@ -0,0 +1,102 @@
+//! Asserting on `NOTIFY`s sent by the code under test.
+
+use std::{
+    error::Error,
+    thread,
+    time::{Duration, Instant},
+};
+
+use diesel::{pg::PgNotification, PgConnection, QueryResult};
+
+use crate::{diagnostics, sql::quote_ident, TestDb};
+
+/// How often a waiting listener checks for new notifications.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// A connection of its own listening on channels of the test database,
+/// opened with [`TestDb::listen`].
+///
+/// Postgres only delivers a notification once the transaction sending it
+/// commits, so code under test running inside
+/// [`test_transaction`](TestDb::test_transaction) never notifies.
+pub struct NotificationListener {
+    conn: PgConnection,
+}
+
+impl TestDb {
+    /// Start listening on `channels`, e.g. to assert that a code path
+    /// invalidates a cache:
+    ///
+    /// ```no_run
+    /// # use std::time::Duration;
+    /// # use diesel_database_tester::TestDb;
+    /// let tdb = TestDb::builder().build();
+    /// let mut listener = tdb.listen(&["todos_changed"]);
+    /// // ... update a todo ...
+    /// let received = listener.received_notifications(Duration::from_secs(1));
+    /// assert_eq!(received[0].payload, "1");
+    /// ```
+    pub fn listen(&self, channels: &[&str]) -> NotificationListener {
+        self.try_listen(channels)
+            .unwrap_or_else(|e| panic!("Failed to listen on {:?}: {}", channels, e))
+    }
+
+    /// Like [`listen`](Self::listen), returning the error instead of
+    /// panicking.
+    pub fn try_listen(
+        &self,
+        channels: &[&str],
+    ) -> Result<NotificationListener, Box<dyn Error + Send + Sync>> {
+        let mut listener = NotificationListener {
+            conn: self.try_connect()?,
+        };
+        for channel in channels {
+            listener.listen(channel)?;
+        }
+        Ok(listener)
+    }
+}
+
+impl NotificationListener {
+    /// Listen on `channel` as well.
+    pub fn listen(&mut self, channel: &str) -> QueryResult<()> {
+        diagnostics::execute(&mut self.conn, &format!("LISTEN {}", quote_ident(channel)))?;
+        Ok(())
+    }
+
+    /// Stop listening on `channel`.
+    pub fn unlisten(&mut self, channel: &str) -> QueryResult<()> {
+        diagnostics::execute(
+            &mut self.conn,
+            &format!("UNLISTEN {}", quote_ident(channel)),
+        )?;
+        Ok(())
+    }
+
+    /// The notifications received since the last call, in the order they
+    /// were sent, waiting up to `timeout` for the first if there are none
+    /// yet. Empty if nothing arrived in time.
+    pub fn received_notifications(&mut self, timeout: Duration) -> Vec<PgNotification> {
+        self.try_received_notifications(timeout)
+            .unwrap_or_else(|e| panic!("Failed to receive notifications: {}", e))
+    }
+
+    /// Like [`received_notifications`](Self::received_notifications),
+    /// returning the error instead of panicking.
+    pub fn try_received_notifications(
+        &mut self,
+        timeout: Duration,
+    ) -> QueryResult<Vec<PgNotification>> {
+        let start = Instant::now();
+        loop {
+            let received = self
+                .conn
+                .notifications_iter()
+                .collect::<QueryResult<Vec<_>>>()?;
+            if !received.is_empty() || start.elapsed() >= timeout {
+                return Ok(received);
+            }
+            thread::sleep(POLL_INTERVAL.min(timeout.saturating_sub(start.elapsed())));
+        }
+    }
+}
@@ -0,0 +1,113 @@
+//! Password lookup in the libpq password file (`~/.pgpass`).
+//!
+//! See <https://www.postgresql.org/docs/current/libpq-pgpass.html> for the
+//! format: one `hostname:port:database:username:password` entry per line,
+//! where any of the first four fields may be `*`.
+
+use std::{env, fs, path::PathBuf};
+
+use log::warn;
+
+/// Find the password for the given connection parameters, the way libpq does.
+pub(crate) fn lookup(host: &str, port: u16, dbname: &str, user: &str) -> Option<String> {
+    let path = passfile()?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = fs::metadata(&path).ok()?.permissions().mode();
+        if mode & 0o077 != 0 {
+            warn!(
+                "Password file {} has group or world access; permissions should be u=rw (0600) or less",
+                path.display()
+            );
+            return None;
+        }
+    }
+    let contents = fs::read_to_string(path).ok()?;
+    find_password(&contents, host, port, dbname, user)
+}
+
+fn passfile() -> Option<PathBuf> {
+    if let Some(path) = env::var_os("PGPASSFILE") {
+        return Some(path.into());
+    }
+    if cfg!(windows) {
+        env::var_os("APPDATA").map(|dir| PathBuf::from(dir).join("postgresql/pgpass.conf"))
+    } else {
+        env::var_os("HOME").map(|dir| PathBuf::from(dir).join(".pgpass"))
+    }
+}
+
+fn find_password(
+    contents: &str,
+    host: &str,
+    port: u16,
+    dbname: &str,
+    user: &str,
+) -> Option<String> {
+    let port = port.to_string();
+    // a unix socket directory matches the "localhost" entries, like libpq
+    let host = if host.starts_with('/') {
+        "localhost"
+    } else {
+        host
+    };
+    contents
+        .lines()
+        .filter(|line| !line.trim_start().starts_with('#'))
+        .map(split_fields)
+        .filter(|fields| fields.len() == 5)
+        .find(|fields| {
+            [host, port.as_str(), dbname, user]
+                .iter()
+                .zip(fields)
+                .all(|(value, field)| field == "*" || field == value)
+        })
+        .map(|mut fields| fields.remove(4))
+}
+
+/// Split a line on unescaped `:`, resolving `\:` and `\\` escapes.
+fn split_fields(line: &str) -> Vec<String> {
+    let mut fields = vec![String::new()];
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                if let Some(next) = chars.next() {
+                    fields.last_mut().unwrap().push(next);
+                }
+            }
+            ':' if fields.len() < 5 => fields.push(String::new()),
+            c => fields.last_mut().unwrap().push(c),
+        }
+    }
+    fields
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pgpass_entries_should_match_like_libpq() {
+        let contents = r#"
+# comment
+otherhost:5432:*:postgres:nope
+localhost:15432:*:postgres:p\:ss\\word
+*:*:*:*:fallback
+"#;
+        assert_eq!(
+            find_password(contents, "localhost", 15432, "test_1", "postgres").as_deref(),
+            Some(r"p:ss\word")
+        );
+        assert_eq!(
+            find_password(contents, "/var/run/postgresql", 15432, "x", "postgres").as_deref(),
+            Some(r"p:ss\word")
+        );
+        assert_eq!(
+            find_password(contents, "localhost", 5432, "x", "postgres").as_deref(),
+            Some("fallback")
+        );
+        assert_eq!(find_password("", "localhost", 5432, "x", "postgres"), None);
+    }
+}
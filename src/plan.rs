@@ -0,0 +1,268 @@
+use std::{fmt, sync::Arc};
+
+use diesel::migration::MigrationSource;
+
+use crate::{
+    builder::PROVIDED_USER,
+    connection::{percent_encode, redact_url},
+    hooks::MigrationHook,
+    maintenance, sql, stale, template, TestDbBuilder,
+};
+
+/// A single operation `TestDb` would perform.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlannedStep {
+    /// What the step does, e.g. `connect to the server`.
+    pub description: String,
+    /// The exact SQL statement, if the step runs one.
+    pub sql: Option<String>,
+}
+
+/// Everything a [`TestDb`](crate::TestDb) would do for a builder, computed
+/// without touching the server. Printing it with `{}` lists one step per line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DryRun {
+    pub dbname: String,
+    pub steps: Vec<PlannedStep>,
+}
+
+impl DryRun {
+    pub(crate) fn new(builder: &TestDbBuilder) -> Self {
+        let dbname = builder.database_name();
+        let server_url = builder
+            .planned_connection_config()
+            .map_err(Into::into)
+            .and_then(|config| config.server_url())
+            .map(|url| redact_url(&url).replacen(&percent_encode(PROVIDED_USER), PROVIDED_USER, 1))
+            .unwrap_or_else(|e| format!("<unresolved: {}>", e));
+
+        let profile = builder
+            .resolved_profile()
+            .unwrap_or_else(|e| panic!("{}", e));
+        let setup_sql = builder.setup_sql(profile.as_ref());
+        let template = builder
+            .uses_shared_template()
+            .then(|| template::name(&builder.migrations, &setup_sql));
+        let mut steps = vec![step(format!("connect to {}", server_url), None)];
+        if let Some(template) = &template {
+            steps.push(step(
+                format!("build template database {} unless it exists", template),
+                None,
+            ));
+        }
+        steps.extend([
+            step(
+                "create the test database".into(),
+                Some(sql::create_database(
+                    &dbname,
+                    template.as_deref(),
+                    Some(&builder.database_options),
+                )),
+            ),
+            step(format!("connect to database {}", dbname), None),
+            step(
+                "record the creation time".into(),
+                Some(stale::mark_created(&dbname)),
+            ),
+        ]);
+        let cached = builder
+            .schema_cache_path(&setup_sql)
+            .filter(|path| path.exists());
+        match (cached, builder.migrations.migrations()) {
+            _ if template.is_some() => {}
+            (Some(path), _) => steps.push(step(
+                format!("restore cached schema from {}", path.display()),
+                None,
+            )),
+            (None, Ok(migrations)) => {
+                steps.extend(
+                    setup_sql
+                        .iter()
+                        .map(|sql| step("run setup SQL".into(), Some(sql.clone()))),
+                );
+                steps.extend(hook_steps("before migrations", &builder.before_migrations));
+                steps.extend(
+                    migrations
+                        .iter()
+                        .map(|migration| step(format!("run migration {}", migration.name()), None)),
+                );
+                steps.extend(hook_steps("after migrations", &builder.after_migrations));
+            }
+            (None, Err(e)) => steps.push(step(format!("<failed to list migrations: {}>", e), None)),
+        }
+        steps.extend(
+            builder
+                .fixtures
+                .iter()
+                .map(|path| step(format!("load fixture {}", path.display()), None)),
+        );
+        steps.extend(
+            builder
+                .seeds
+                .iter()
+                .map(|_| step("run a seed".into(), None)),
+        );
+        let (autovacuum, database_settings) =
+            maintenance::split_autovacuum(&builder.database_settings);
+        if autovacuum == Some(false) {
+            steps.push(step(
+                "disable autovacuum".into(),
+                Some(maintenance::DISABLE_AUTOVACUUM.into()),
+            ));
+        }
+        if builder.analyze_after_seed {
+            steps.push(step(
+                "collect planner statistics".into(),
+                Some("ANALYZE".into()),
+            ));
+        }
+        if let Some(role) = &builder.app_role {
+            steps.extend(
+                role.setup_sql(&dbname, "********", &dbname)
+                    .into_iter()
+                    .map(|sql| step("set up the app role".into(), Some(sql))),
+            );
+        }
+        steps.extend(database_settings.iter().map(|(name, value)| {
+            step(
+                "apply a database setting".into(),
+                Some(sql::alter_database_set(&dbname, name, value)),
+            )
+        }));
+        if let Some(profile) = &profile {
+            steps.extend(
+                profile
+                    .settings_sql(&dbname)
+                    .into_iter()
+                    .map(|sql| step("apply a profile setting".into(), Some(sql))),
+            );
+        }
+        if let Some(quota) = &builder.quota {
+            steps.extend(
+                quota
+                    .statements(&dbname)
+                    .into_iter()
+                    .map(|sql| step("apply a quota".into(), Some(sql))),
+            );
+        }
+        steps.extend([
+            step(format!("connect to {}", server_url), None),
+            step(
+                "terminate remaining connections".into(),
+                Some(sql::terminate_connections(&dbname)),
+            ),
+            step(
+                "drop the test database".into(),
+                Some(sql::drop_database(&dbname)),
+            ),
+        ]);
+        if builder.app_role.is_some() {
+            steps.push(step(
+                "drop the app role".into(),
+                Some(sql::drop_role(&dbname)),
+            ));
+        }
+        Self { dbname, steps }
+    }
+}
+
+fn step(description: String, sql: Option<String>) -> PlannedStep {
+    PlannedStep { description, sql }
+}
+
+fn hook_steps<'a>(
+    when: &'a str,
+    hooks: &'a [Arc<dyn MigrationHook>],
+) -> impl Iterator<Item = PlannedStep> + 'a {
+    hooks
+        .iter()
+        .map(move |hook| step(format!("run a hook {}", when), hook.sql()))
+}
+
+impl fmt::Display for DryRun {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, step) in self.steps.iter().enumerate() {
+            write!(f, "{:>2}. {}", i + 1, step.description)?;
+            if let Some(sql) = &step.sql {
+                write!(f, ": {};", sql)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dry_run_should_list_every_step_without_a_server() {
+        let plan = TestDbBuilder::new()
+            .host("unreachable.invalid")
+            .password("secret")
+            .named("plan")
+            .dry_run();
+        let printed = plan.to_string();
+        assert!(plan.dbname.ends_with("_plan"));
+        assert!(printed.contains(&format!(r#"CREATE DATABASE "{}";"#, plan.dbname)));
+        assert!(printed.contains("run migration 2022-12-08-031140_todo"));
+        assert!(printed.contains(&format!(r#"DROP DATABASE "{}";"#, plan.dbname)));
+        assert!(!printed.contains("secret"));
+    }
+
+    #[test]
+    fn dry_run_should_not_ask_the_credential_provider() {
+        let plan = TestDbBuilder::new()
+            .host("db.internal")
+            .port(5433)
+            .credentials(
+                || -> Result<crate::Credentials, Box<dyn std::error::Error + Send + Sync>> {
+                    panic!("the dry run fetched credentials")
+                },
+            )
+            .dry_run();
+        let printed = plan.to_string();
+        assert!(
+            printed.contains("connect to postgres://<from provider>@db.internal:5433\n"),
+            "{}",
+            printed
+        );
+    }
+
+    #[test]
+    fn dry_run_should_list_hooks_around_the_migrations() {
+        let plan = TestDbBuilder::new()
+            .shared_template(true)
+            .before_migrations("CREATE ROLE reader")
+            .after_migrations(|_: &mut diesel::PgConnection| Ok::<_, String>(()))
+            .dry_run();
+        let printed = plan.to_string();
+        assert!(!printed.contains("template"), "{}", printed);
+        let before = printed
+            .find("run a hook before migrations: CREATE ROLE reader;")
+            .unwrap();
+        let migration = printed.find("run migration").unwrap();
+        let after = printed.find("run a hook after migrations\n").unwrap();
+        assert!(before < migration && migration < after, "{}", printed);
+    }
+
+    #[test]
+    fn dry_run_should_list_database_settings_after_the_seeds() {
+        let plan = TestDbBuilder::new()
+            .with_database_settings(&[("autovacuum", "off"), ("jit", "off")])
+            .analyze_after_seed(true)
+            .dry_run();
+        let printed = plan.to_string();
+        let disable = printed.find("disable autovacuum: DO $$").unwrap();
+        let analyze = printed.find("collect planner statistics").unwrap();
+        let setting = printed
+            .find(&format!(
+                r#"apply a database setting: ALTER DATABASE "{}" SET jit = 'off';"#,
+                plan.dbname
+            ))
+            .unwrap();
+        assert!(disable < analyze && analyze < setting, "{}", printed);
+        assert!(!printed.contains("SET autovacuum"), "{}", printed);
+    }
+}
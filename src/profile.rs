@@ -0,0 +1,131 @@
+//! Reusable database configurations registered once and applied by name.
+
+use std::{
+    collections::HashMap,
+    sync::{Mutex, OnceLock},
+};
+
+use crate::{
+    sql::{self, quote_ident},
+    TestDbError,
+};
+
+static PROFILES: OnceLock<Mutex<HashMap<String, Profile>>> = OnceLock::new();
+
+fn profiles() -> &'static Mutex<HashMap<String, Profile>> {
+    PROFILES.get_or_init(Default::default)
+}
+
+/// Settings, extensions and roles describing how a production database is
+/// configured, registered under a name and selected per test database with
+/// [`TestDbBuilder::profile`](crate::TestDbBuilder::profile).
+///
+/// ```no_run
+/// use diesel_database_tester::{Profile, TestDb};
+///
+/// Profile::new()
+///     .setting("work_mem", "64MB")
+///     .setting("default_transaction_isolation", "repeatable read")
+///     .extension("pg_trgm")
+///     .role("analyst")
+///     .register("analytics");
+///
+/// let tdb = TestDb::builder().profile("analytics").build();
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Profile {
+    settings: Vec<(String, String)>,
+    extensions: Vec<String>,
+    roles: Vec<String>,
+}
+
+impl Profile {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set a GUC as the database default, with `ALTER DATABASE ... SET`.
+    pub fn setting(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.settings.push((name.into(), value.into()));
+        self
+    }
+
+    /// Create an extension before the migrations run.
+    pub fn extension(mut self, name: impl Into<String>) -> Self {
+        self.extensions.push(name.into());
+        self
+    }
+
+    /// Create a `NOLOGIN` role, unless it exists, before the migrations run,
+    /// so they can grant privileges to it. Roles are shared by the whole
+    /// server and left in place.
+    pub fn role(mut self, name: impl Into<String>) -> Self {
+        self.roles.push(name.into());
+        self
+    }
+
+    /// Make the profile available to every builder under `name`, replacing
+    /// any profile registered before under the same name.
+    pub fn register(self, name: impl Into<String>) {
+        profiles().lock().unwrap().insert(name.into(), self);
+    }
+
+    /// The profile registered under `name`.
+    pub(crate) fn lookup(name: &str) -> Result<Profile, TestDbError> {
+        profiles()
+            .lock()
+            .unwrap()
+            .get(name)
+            .cloned()
+            .ok_or_else(|| TestDbError::Config(format!(r#"Profile "{}" isn't registered"#, name)))
+    }
+
+    /// Statements run before the migrations.
+    pub(crate) fn setup_sql(&self) -> Vec<String> {
+        let roles = self.roles.iter().map(|role| {
+            // concurrent creation of the same role can also fail with unique_violation
+            format!(
+                "DO $$ BEGIN CREATE ROLE {} NOLOGIN; \
+                 EXCEPTION WHEN duplicate_object OR unique_violation THEN NULL; END $$",
+                quote_ident(role)
+            )
+        });
+        let extensions = self
+            .extensions
+            .iter()
+            .map(|extension| sql::create_extension(extension));
+        roles.chain(extensions).collect()
+    }
+
+    /// Statements setting the defaults of `dbname` once it is set up.
+    pub(crate) fn settings_sql(&self, dbname: &str) -> Vec<String> {
+        self.settings
+            .iter()
+            .map(|(name, value)| sql::alter_database_set(dbname, name, value))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn profile_should_translate_to_sql() {
+        let profile = Profile::new()
+            .setting("work_mem", "64MB")
+            .extension("pg_trgm")
+            .role("analyst");
+        assert_eq!(
+            profile.setup_sql(),
+            [
+                r#"DO $$ BEGIN CREATE ROLE "analyst" NOLOGIN; EXCEPTION WHEN duplicate_object OR unique_violation THEN NULL; END $$"#,
+                r#"CREATE EXTENSION IF NOT EXISTS "pg_trgm""#,
+            ]
+        );
+        assert_eq!(
+            profile.settings_sql("test_1"),
+            [r#"ALTER DATABASE "test_1" SET work_mem = '64MB'"#]
+        );
+    }
+}
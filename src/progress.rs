@@ -0,0 +1,16 @@
+//! Progress reporting while the migrations run during setup.
+
+use std::{sync::Arc, time::Duration};
+
+/// One migration that just finished running.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MigrationProgress {
+    /// The migration name, e.g. `2022-12-08-031140_todo`.
+    pub name: String,
+    /// 1-based position among the migrations being run.
+    pub index: usize,
+    pub total: usize,
+    pub duration: Duration,
+}
+
+pub(crate) type OnMigration = Arc<dyn Fn(&MigrationProgress) + Send + Sync>;
@@ -0,0 +1,106 @@
+//! Looking at a test database by hand: what it prints as, and `psql` into
+//! it, without passwords ending up in test output.
+
+use std::{
+    error::Error,
+    fmt, io,
+    process::{Command, ExitStatus},
+};
+
+use crate::{connection::ConnectionConfig, with_database, TestDb};
+
+impl TestDb {
+    /// A `psql` command line connecting to the database as the owner, to
+    /// paste into a shell while a test is paused or the database
+    /// [kept](Self::keep_on_drop). The password is left out: psql asks for it,
+    /// unless `PGPASSWORD` or `~/.pgpass` has it, see [`psql`](Self::psql).
+    ///
+    /// ```no_run
+    /// # use diesel_database_tester::TestDb;
+    /// let tdb = TestDb::builder().build();
+    /// eprintln!("{}", tdb.psql_command());
+    /// // psql 'postgres://postgres@localhost:5432/test_...'
+    /// ```
+    pub fn psql_command(&self) -> String {
+        let url = self
+            .passwordless_url()
+            .unwrap_or_else(|e| panic!("Failed to resolve connection endpoint: {}", e));
+        format!("psql {}", shell_quote(&url))
+    }
+
+    /// Run an interactive `psql` on the database in the terminal of the test
+    /// and wait for it to exit, e.g. with `cargo test -- --nocapture` while
+    /// debugging a test. The password is handed over in `PGPASSWORD`.
+    pub fn psql(&self) -> io::Result<ExitStatus> {
+        let config = self.connection_config();
+        let password = match &config.credentials {
+            Some(credentials) => credentials.current().map_err(io::Error::other)?.password,
+            None => config.password.clone(),
+        };
+        let url = self.passwordless_url().map_err(io::Error::other)?;
+        Command::new("psql")
+            .arg(url)
+            .env("PGPASSWORD", password)
+            .status()
+    }
+
+    fn passwordless_url(&self) -> Result<String, Box<dyn Error + Send + Sync>> {
+        let config = self.connection_config();
+        let user = match &config.credentials {
+            Some(credentials) => credentials.current()?.user,
+            None => config.user.clone(),
+        };
+        let config = ConnectionConfig {
+            user,
+            password: String::new(),
+            credentials: None,
+            ..config
+        };
+        Ok(with_database(&config.server_url()?, &self.dbname))
+    }
+}
+
+/// `value` as a single POSIX shell word.
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+/// `user@host:port/dbname`, e.g. for test output.
+impl fmt::Display for TestDb {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}@{}:{}/{}",
+            self.user, self.host, self.port, self.dbname
+        )
+    }
+}
+
+impl fmt::Debug for TestDb {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TestDb")
+            .field("host", &self.host)
+            .field("port", &self.port)
+            .field("user", &self.user)
+            .field("password", &"********")
+            .field("sslmode", &self.sslmode)
+            .field("dbname", &self.dbname)
+            .field(
+                "replica",
+                &self.replica.as_ref().map(|replica| &replica.dbname),
+            )
+            .field("keep", &self.keep)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shell_words_should_survive_quotes() {
+        assert_eq!(shell_quote("postgres://a@b/c"), "'postgres://a@b/c'");
+        assert_eq!(shell_quote("it's"), r"'it'\''s'");
+    }
+}
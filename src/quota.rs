@@ -0,0 +1,99 @@
+//! Resource limits applied to test databases.
+
+use std::time::Duration;
+
+use crate::sql;
+
+/// Limits set on a test database once it is migrated, so a runaway test hits
+/// them in CI instead of exhausting the shared server. Each limit left `None`
+/// keeps the server default. Start from a preset and adjust:
+///
+/// ```no_run
+/// use std::time::Duration;
+/// use diesel_database_tester::{Quota, TestDb};
+///
+/// let tdb = TestDb::builder()
+///     .quota(Quota {
+///         statement_timeout: Some(Duration::from_secs(30)),
+///         ..Quota::strict()
+///     })
+///     .build();
+/// ```
+///
+/// `temp_file_limit` can only be set by a superuser.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Quota {
+    /// `CONNECTION LIMIT` of the database.
+    pub connection_limit: Option<u32>,
+    pub statement_timeout: Option<Duration>,
+    pub lock_timeout: Option<Duration>,
+    pub idle_in_transaction_session_timeout: Option<Duration>,
+    /// Bytes of temporary files a session may use, e.g. for sorts spilling to
+    /// disk.
+    pub temp_file_limit: Option<u64>,
+}
+
+impl Quota {
+    /// Tight limits for unit-sized tests.
+    pub fn strict() -> Self {
+        Self {
+            connection_limit: Some(10),
+            statement_timeout: Some(Duration::from_secs(5)),
+            lock_timeout: Some(Duration::from_secs(1)),
+            idle_in_transaction_session_timeout: Some(Duration::from_secs(10)),
+            temp_file_limit: Some(64 << 20),
+        }
+    }
+
+    /// Generous limits for integration tests that do real work, still low
+    /// enough to stop a hung or runaway test.
+    pub fn relaxed() -> Self {
+        Self {
+            connection_limit: Some(50),
+            statement_timeout: Some(Duration::from_secs(60)),
+            lock_timeout: Some(Duration::from_secs(10)),
+            idle_in_transaction_session_timeout: Some(Duration::from_secs(60)),
+            temp_file_limit: Some(1 << 30),
+        }
+    }
+
+    /// The statements applying the limits to `dbname`.
+    pub(crate) fn statements(&self, dbname: &str) -> Vec<String> {
+        let millis = |name: &str, timeout: Option<Duration>| {
+            timeout.map(|t| sql::alter_database_set(dbname, name, &format!("{}ms", t.as_millis())))
+        };
+        self.connection_limit
+            .map(|limit| sql::connection_limit(dbname, limit))
+            .into_iter()
+            .chain(millis("statement_timeout", self.statement_timeout))
+            .chain(millis("lock_timeout", self.lock_timeout))
+            .chain(millis(
+                "idle_in_transaction_session_timeout",
+                self.idle_in_transaction_session_timeout,
+            ))
+            .chain(self.temp_file_limit.map(|bytes| {
+                sql::alter_database_set(dbname, "temp_file_limit", &format!("{}kB", bytes / 1024))
+            }))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn presets_should_translate_to_alter_database() {
+        assert_eq!(Quota::default().statements("test_1"), Vec::<String>::new());
+        assert_eq!(
+            Quota::strict().statements("test_1"),
+            [
+                r#"ALTER DATABASE "test_1" CONNECTION LIMIT 10"#,
+                r#"ALTER DATABASE "test_1" SET statement_timeout = '5000ms'"#,
+                r#"ALTER DATABASE "test_1" SET lock_timeout = '1000ms'"#,
+                r#"ALTER DATABASE "test_1" SET idle_in_transaction_session_timeout = '10000ms'"#,
+                r#"ALTER DATABASE "test_1" SET temp_file_limit = '65536kB'"#,
+            ]
+        );
+    }
+}
@@ -0,0 +1,253 @@
+//! Credential provider for AWS RDS IAM database authentication.
+//!
+//! Auth tokens are SigV4-presigned `connect` requests, valid for 15 minutes.
+//! AWS credentials are read from `AWS_ACCESS_KEY_ID`, `AWS_SECRET_ACCESS_KEY`
+//! and, for temporary credentials, `AWS_SESSION_TOKEN`.
+
+use std::{
+    env,
+    error::Error,
+    sync::Mutex,
+    time::{Duration, SystemTime},
+};
+
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    connection::percent_encode,
+    credentials::{CredentialProvider, Credentials, REFRESH_MARGIN},
+};
+
+/// How long RDS accepts a generated token.
+const TOKEN_TTL: Duration = Duration::from_secs(15 * 60);
+const SERVICE: &str = "rds-db";
+
+/// Generates RDS IAM auth tokens for `user`, regenerating them before they
+/// expire.
+///
+/// ```no_run
+/// use diesel_database_tester::{RdsIamCredentials, TestDb};
+///
+/// let host = "mydb.123456789012.eu-west-1.rds.amazonaws.com";
+/// let tdb = TestDb::builder()
+///     .host(host)
+///     .sslmode("require")
+///     .credentials(RdsIamCredentials::new(host, 5432, "eu-west-1", "iam_user"))
+///     .build();
+/// ```
+#[derive(Debug)]
+pub struct RdsIamCredentials {
+    host: String,
+    port: u16,
+    region: String,
+    user: String,
+    cached: Mutex<Option<Credentials>>,
+    /// Generates a new token, replaced in tests.
+    signer: fn(&RdsIamCredentials) -> Result<String, Box<dyn Error + Send + Sync + 'static>>,
+}
+
+impl RdsIamCredentials {
+    pub fn new(
+        host: impl Into<String>,
+        port: u16,
+        region: impl Into<String>,
+        user: impl Into<String>,
+    ) -> Self {
+        Self {
+            host: host.into(),
+            port,
+            region: region.into(),
+            user: user.into(),
+            cached: Mutex::new(None),
+            signer: Self::generate_token,
+        }
+    }
+
+    fn generate_token(&self) -> Result<String, Box<dyn Error + Send + Sync + 'static>> {
+        let access_key = env::var("AWS_ACCESS_KEY_ID")?;
+        let secret_key = env::var("AWS_SECRET_ACCESS_KEY")?;
+        let session_token = env::var("AWS_SESSION_TOKEN").ok();
+        Ok(auth_token(
+            &self.host,
+            self.port,
+            &self.region,
+            &self.user,
+            &access_key,
+            &secret_key,
+            session_token.as_deref(),
+            Utc::now(),
+        ))
+    }
+}
+
+impl CredentialProvider for RdsIamCredentials {
+    fn credentials(&self) -> Result<Credentials, Box<dyn Error + Send + Sync + 'static>> {
+        let mut cached = self.cached.lock().unwrap();
+        match cached.as_ref() {
+            Some(credentials) if !credentials.expires_within(REFRESH_MARGIN) => {
+                Ok(credentials.clone())
+            }
+            _ => {
+                let credentials = Credentials {
+                    user: self.user.clone(),
+                    password: (self.signer)(self)?,
+                    expires_at: Some(SystemTime::now() + TOKEN_TTL),
+                };
+                *cached = Some(credentials.clone());
+                Ok(credentials)
+            }
+        }
+    }
+
+    fn invalidate(&self) {
+        *self.cached.lock().unwrap() = None;
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn auth_token(
+    host: &str,
+    port: u16,
+    region: &str,
+    user: &str,
+    access_key: &str,
+    secret_key: &str,
+    session_token: Option<&str>,
+    now: DateTime<Utc>,
+) -> String {
+    let date = now.format("%Y%m%d").to_string();
+    let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+    let scope = format!("{}/{}/{}/aws4_request", date, region, SERVICE);
+    let endpoint = format!("{}:{}", host, port);
+
+    let mut params = vec![
+        ("Action", "connect".to_string()),
+        ("DBUser", user.to_string()),
+        ("X-Amz-Algorithm", "AWS4-HMAC-SHA256".to_string()),
+        ("X-Amz-Credential", format!("{}/{}", access_key, scope)),
+        ("X-Amz-Date", amz_date.clone()),
+        ("X-Amz-Expires", TOKEN_TTL.as_secs().to_string()),
+        ("X-Amz-SignedHeaders", "host".to_string()),
+    ];
+    if let Some(token) = session_token {
+        params.push(("X-Amz-Security-Token", token.to_string()));
+    }
+    params.sort();
+    let query = params
+        .iter()
+        .map(|(k, v)| format!("{}={}", percent_encode(k), percent_encode(v)))
+        .collect::<Vec<_>>()
+        .join("&");
+
+    let canonical_request = format!(
+        "GET\n/\n{}\nhost:{}\n\nhost\n{}",
+        query,
+        endpoint,
+        hex(&Sha256::digest(b""))
+    );
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+        amz_date,
+        scope,
+        hex(&Sha256::digest(canonical_request.as_bytes()))
+    );
+    let key = signing_key(secret_key, &date, region, SERVICE);
+    let signature = hex(&hmac(&key, string_to_sign.as_bytes()));
+
+    format!("{}/?{}&X-Amz-Signature={}", endpoint, query, signature)
+}
+
+fn signing_key(secret_key: &str, date: &str, region: &str, service: &str) -> Vec<u8> {
+    let key = hmac(format!("AWS4{}", secret_key).as_bytes(), date.as_bytes());
+    let key = hmac(&key, region.as_bytes());
+    let key = hmac(&key, service.as_bytes());
+    hmac(&key, b"aws4_request")
+}
+
+fn hmac(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any size");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    use chrono::TimeZone;
+
+    use super::*;
+    use crate::credentials::RefreshingCredentials;
+
+    #[test]
+    fn forced_refresh_should_sign_a_new_token() {
+        static SIGNED: AtomicUsize = AtomicUsize::new(0);
+        fn counting_signer(
+            _: &RdsIamCredentials,
+        ) -> Result<String, Box<dyn Error + Send + Sync + 'static>> {
+            Ok(format!(
+                "token-{}",
+                SIGNED.fetch_add(1, Ordering::SeqCst) + 1
+            ))
+        }
+        let rds = Arc::new(RdsIamCredentials {
+            signer: counting_signer,
+            ..RdsIamCredentials::new("localhost", 5432, "eu-west-1", "iam_user")
+        });
+        let initial = rds.credentials().unwrap();
+        assert_eq!(rds.credentials().unwrap(), initial);
+        assert_eq!(SIGNED.load(Ordering::SeqCst), 1);
+
+        let credentials = RefreshingCredentials::new(initial, rds.clone());
+        credentials.force_refresh().unwrap();
+        assert_eq!(SIGNED.load(Ordering::SeqCst), 2);
+        assert_eq!(credentials.current().unwrap().password, "token-2");
+    }
+
+    #[test]
+    fn signing_key_should_match_aws_example() {
+        // https://docs.aws.amazon.com/general/latest/gr/signature-v4-examples.html
+        let key = signing_key(
+            "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY",
+            "20120215",
+            "us-east-1",
+            "iam",
+        );
+        assert_eq!(
+            hex(&key),
+            "f4780e2d9f65fa895f9c67b32ce1baf0b0d8a43505a000a1a9e090d414db404d"
+        );
+    }
+
+    #[test]
+    fn auth_token_should_be_a_presigned_connect_request() {
+        let now = Utc.with_ymd_and_hms(2022, 12, 8, 3, 11, 40).unwrap();
+        let token = auth_token(
+            "mydb.example.rds.amazonaws.com",
+            5432,
+            "eu-west-1",
+            "iam_user",
+            "AKIDEXAMPLE",
+            "secret",
+            None,
+            now,
+        );
+        assert!(token.starts_with(
+            "mydb.example.rds.amazonaws.com:5432/?Action=connect&DBUser=iam_user&X-Amz-Algorithm=AWS4-HMAC-SHA256"
+        ));
+        assert!(token.contains(
+            "X-Amz-Credential=AKIDEXAMPLE%2F20221208%2Feu-west-1%2Frds-db%2Faws4_request"
+        ));
+        assert!(token.contains("X-Amz-Date=20221208T031140Z&X-Amz-Expires=900"));
+        assert_eq!(token.split("X-Amz-Signature=").nth(1).unwrap().len(), 64);
+    }
+}
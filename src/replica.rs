@@ -0,0 +1,71 @@
+//! A read-only copy of a test database standing in for a replica, see
+//! [`TestDbBuilder::replica`](crate::TestDbBuilder::replica).
+
+use crate::{
+    connect_to,
+    fork::{replace_database, revoke_app_role},
+    sql, Pool, TestDb, TestDbError,
+};
+
+impl TestDb {
+    /// A pool to the replica, whose transactions are read-only: writes fail
+    /// with `cannot execute ... in a read-only transaction`.
+    ///
+    /// ```no_run
+    /// # use diesel_database_tester::TestDb;
+    /// let tdb = TestDb::builder().replica().build();
+    /// let primary = tdb.pool();
+    /// let replica = tdb.replica_pool();
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// If the database was built without [`replica`](crate::TestDbBuilder::replica).
+    pub fn replica_pool(&self) -> Pool {
+        self.try_replica_pool().unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Like [`replica_pool`](Self::replica_pool), returning the error instead
+    /// of panicking.
+    pub fn try_replica_pool(&self) -> Result<Pool, TestDbError> {
+        self.replica()?.try_pool()
+    }
+
+    /// Copy the primary's current state to the replica again, e.g. after
+    /// writing the rows a test then reads from the replica. Open replica
+    /// connections are terminated; pools reconnect on their next checkout.
+    /// The old replica is only dropped once the copy is complete, so a failed
+    /// sync leaves it as it was.
+    pub fn sync_replica(&self) -> Result<(), TestDbError> {
+        let replica = self.replica()?;
+        let server_url = self.server_url();
+        let mut conn = connect_to(&server_url)?;
+        let staging = self.namer.name(Some("sync")).map_err(TestDbError::Config)?;
+        replace_database(&mut conn, &self.dbname, &replica.dbname, &staging)
+            .map_err(TestDbError::CreateDatabase)?;
+        if self.app_password.is_some() {
+            revoke_app_role(&server_url, &replica.dbname, &self.dbname)?;
+        }
+        Ok(())
+    }
+
+    /// The replica for [`TestDbBuilder::replica`](crate::TestDbBuilder::replica),
+    /// a copy of this database with read-only sessions.
+    pub(crate) fn create_replica(&self) -> Result<TestDb, TestDbError> {
+        let mut replica = self.copy("replica")?;
+        replica
+            .config
+            .session_sql
+            .push(sql::set("default_transaction_read_only", "on"));
+        Ok(replica)
+    }
+
+    fn replica(&self) -> Result<&TestDb, TestDbError> {
+        self.replica.as_deref().ok_or_else(|| {
+            TestDbError::Config(format!(
+                "{} has no replica, build it with TestDbBuilder::replica",
+                self.dbname
+            ))
+        })
+    }
+}
@@ -0,0 +1,221 @@
+//! Per-test database timing report written when the process exits.
+//!
+//! Set `TESTDB_TIMING_REPORT` to a file path; a JUnit XML report is written if
+//! it ends in `.xml`, JSON otherwise. Each entry maps a created database and
+//! the test that owned it (the test thread name, when known) to its setup and
+//! teardown durations, the setup broken down like [`SetupStats`].
+
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    sync::{Mutex, OnceLock, PoisonError},
+    thread,
+    time::Duration,
+};
+
+use log::warn;
+use serde::Serialize;
+
+use crate::SetupStats;
+
+#[derive(Debug, Clone, Default, Serialize)]
+struct DatabaseTiming {
+    database: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    test: Option<String>,
+    setup_ms: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    create_database_ms: Option<f64>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    migrations: Vec<MigrationTiming>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    fixtures_ms: Option<f64>,
+    teardown_ms: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct MigrationTiming {
+    name: String,
+    ms: f64,
+}
+
+struct Report {
+    path: PathBuf,
+    timings: Mutex<Vec<DatabaseTiming>>,
+}
+
+static REPORT: OnceLock<Option<Report>> = OnceLock::new();
+
+fn report() -> Option<&'static Report> {
+    REPORT
+        .get_or_init(|| {
+            let path = PathBuf::from(env::var_os("TESTDB_TIMING_REPORT")?);
+            extern "C" fn write_on_exit() {
+                if let Some(report) = REPORT.get().and_then(Option::as_ref) {
+                    if let Err(e) = report.write() {
+                        warn!("Failed to write timing report {:?}: {}", report.path, e);
+                    }
+                }
+            }
+            unsafe {
+                libc::atexit(write_on_exit);
+            }
+            Some(Report {
+                path,
+                timings: Mutex::new(Vec::new()),
+            })
+        })
+        .as_ref()
+}
+
+/// The test owning the current thread, as named by the libtest harness.
+pub(crate) fn current_test() -> Option<String> {
+    thread::current()
+        .name()
+        .filter(|name| *name != "main")
+        .map(str::to_string)
+}
+
+pub(crate) fn record_setup(database: &str, test: Option<String>, stats: &SetupStats) {
+    if let Some(report) = report() {
+        report.timings.lock().unwrap().push(DatabaseTiming {
+            database: database.to_string(),
+            test,
+            setup_ms: Some(millis(stats.total)),
+            create_database_ms: Some(millis(stats.create_database)),
+            migrations: stats
+                .migrations
+                .iter()
+                .map(|m| MigrationTiming {
+                    name: m.name.clone(),
+                    ms: millis(m.duration),
+                })
+                .collect(),
+            fixtures_ms: Some(millis(stats.fixtures)),
+            teardown_ms: None,
+        });
+    }
+}
+
+pub(crate) fn record_teardown(database: &str, duration: Duration) {
+    if let Some(report) = report() {
+        let mut timings = report
+            .timings
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        match timings.iter_mut().find(|t| t.database == database) {
+            Some(timing) => timing.teardown_ms = Some(millis(duration)),
+            None => timings.push(DatabaseTiming {
+                database: database.to_string(),
+                teardown_ms: Some(millis(duration)),
+                ..Default::default()
+            }),
+        }
+    }
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+impl Report {
+    fn write(&self) -> std::io::Result<()> {
+        let timings = self.timings.lock().unwrap();
+        let contents = if is_xml(&self.path) {
+            junit(&timings)
+        } else {
+            serde_json::to_string_pretty(&*timings)?
+        };
+        fs::write(&self.path, contents)
+    }
+}
+
+fn is_xml(path: &Path) -> bool {
+    path.extension().map(|ext| ext == "xml").unwrap_or(false)
+}
+
+fn junit(timings: &[DatabaseTiming]) -> String {
+    let seconds = |ms: Option<f64>| ms.unwrap_or_default() / 1000.0;
+    let total: f64 = timings
+        .iter()
+        .map(|t| seconds(t.setup_ms) + seconds(t.teardown_ms))
+        .sum();
+    let mut xml = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<testsuite name=\"testdb\" tests=\"{}\" time=\"{:.3}\">\n",
+        timings.len(),
+        total
+    );
+    for t in timings {
+        xml.push_str(&format!(
+            "  <testcase classname=\"{}\" name=\"{}\" time=\"{:.3}\">\n    <properties>\n",
+            escape(t.test.as_deref().unwrap_or("unknown")),
+            escape(&t.database),
+            seconds(t.setup_ms) + seconds(t.teardown_ms),
+        ));
+        let mut phases = vec![("setup".to_string(), t.setup_ms)];
+        if t.create_database_ms.is_some() {
+            phases.push(("create_database".to_string(), t.create_database_ms));
+        }
+        phases.extend(
+            t.migrations
+                .iter()
+                .map(|m| (format!("migration {}", m.name), Some(m.ms))),
+        );
+        if t.fixtures_ms.is_some() {
+            phases.push(("fixtures".to_string(), t.fixtures_ms));
+        }
+        phases.push(("teardown".to_string(), t.teardown_ms));
+        for (name, ms) in phases {
+            xml.push_str(&format!(
+                "      <property name=\"{}\" value=\"{:.3}\"/>\n",
+                escape(&name),
+                seconds(ms)
+            ));
+        }
+        xml.push_str("    </properties>\n  </testcase>\n");
+    }
+    xml.push_str("</testsuite>\n");
+    xml
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timings_should_render_as_junit() {
+        let timings = vec![DatabaseTiming {
+            database: "test_1".into(),
+            test: Some("users::create_flow".into()),
+            setup_ms: Some(1500.0),
+            create_database_ms: Some(200.0),
+            migrations: vec![MigrationTiming {
+                name: "2024-01-01-000000_users".into(),
+                ms: 1000.0,
+            }],
+            fixtures_ms: Some(250.0),
+            teardown_ms: Some(500.0),
+        }];
+        let xml = junit(&timings);
+        assert!(xml.contains(r#"<testsuite name="testdb" tests="1" time="2.000">"#));
+        assert!(
+            xml.contains(r#"<testcase classname="users::create_flow" name="test_1" time="2.000">"#)
+        );
+        assert!(xml.contains(r#"<property name="setup" value="1.500"/>"#));
+        assert!(xml.contains(r#"<property name="create_database" value="0.200"/>"#));
+        assert!(
+            xml.contains(r#"<property name="migration 2024-01-01-000000_users" value="1.000"/>"#)
+        );
+        assert!(xml.contains(r#"<property name="fixtures" value="0.250"/>"#));
+        assert!(is_xml(Path::new("report.xml")));
+        assert!(!is_xml(Path::new("report.json")));
+    }
+}
@@ -0,0 +1,78 @@
+//! Emptying a database between the cases of one test, so a single expensive
+//! [`TestDb`] can be reused without them seeing each other's rows.
+
+use std::error::Error;
+
+use diesel::{sql_query, sql_types::Text, PgConnection, QueryableByName, RunQueryDsl};
+
+use crate::{diagnostics, fixtures, TestDb};
+
+#[derive(QueryableByName)]
+struct Table {
+    #[diesel(sql_type = Text)]
+    name: String,
+}
+
+/// `TRUNCATE` of every user table, or `None` if there are none.
+fn truncate_statement(
+    conn: &mut PgConnection,
+) -> Result<Option<String>, Box<dyn Error + Send + Sync>> {
+    let tables: Vec<Table> = sql_query(
+        "SELECT quote_ident(table_schema) || '.' || quote_ident(table_name) AS name \
+         FROM information_schema.tables \
+         WHERE table_type = 'BASE TABLE' \
+         AND table_schema NOT IN ('pg_catalog', 'information_schema') \
+         AND table_schema NOT LIKE 'pg_temp%' \
+         AND table_name <> '__diesel_schema_migrations' \
+         ORDER BY 1",
+    )
+    .load(conn)?;
+    if tables.is_empty() {
+        return Ok(None);
+    }
+    let names: Vec<_> = tables.into_iter().map(|table| table.name).collect();
+    Ok(Some(format!(
+        "TRUNCATE {} RESTART IDENTITY CASCADE",
+        names.join(", ")
+    )))
+}
+
+impl TestDb {
+    /// Empty every table, restarting their sequences, then load the
+    /// [fixtures](crate::TestDbBuilder::with_fixtures) and run the
+    /// [seeds](crate::TestDbBuilder::with_seed) of the builder again, leaving
+    /// the database as it was right after setup. The migrations are kept.
+    ///
+    /// ```no_run
+    /// # use diesel_database_tester::TestDb;
+    /// let tdb = TestDb::builder().with_fixtures(["fixtures/users.sql"]).build();
+    /// for case in ["empty title", "long title"] {
+    ///     tdb.reset().unwrap();
+    ///     // ... exercise `case` against the baseline data ...
+    /// }
+    /// ```
+    pub fn reset(&self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let mut conn = self.connect();
+        truncate(&mut conn)?;
+        if !self.fixtures.is_empty() {
+            fixtures::load_sql(&mut conn, &self.fixtures)?;
+        }
+        for seed in &self.seeds {
+            seed(&mut conn)?;
+        }
+        Ok(())
+    }
+
+    /// Empty every table, restarting their sequences, without loading the
+    /// fixtures or running the seeds again like [`reset`](Self::reset).
+    pub fn truncate_all(&self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        truncate(&mut self.connect())
+    }
+}
+
+fn truncate(conn: &mut PgConnection) -> Result<(), Box<dyn Error + Send + Sync>> {
+    if let Some(statement) = truncate_statement(conn)? {
+        diagnostics::execute(conn, &statement)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,78 @@
+//! Reverting and re-applying migrations, to exercise down migrations against
+//! seeded data the way a real rollback would.
+
+use std::error::Error;
+
+use diesel::migration::MigrationSource;
+use diesel_migrations::MigrationHarness;
+
+use crate::{diagnostics, TestDb};
+
+impl TestDb {
+    /// Run the down migrations of the `n` most recently applied migrations,
+    /// newest first. Returns the versions that were reverted.
+    pub fn revert_last(&self, n: usize) -> Result<Vec<String>, Box<dyn Error + Send + Sync>> {
+        let mut conn = self.connect();
+        let mut reverted = Vec::with_capacity(n);
+        for _ in 0..n {
+            reverted.push(
+                conn.revert_last_migration(self.migrations.clone())?
+                    .to_string(),
+            );
+        }
+        Ok(reverted)
+    }
+
+    /// Run every migration that isn't applied, e.g. after
+    /// [`revert_last`](Self::revert_last). Returns the versions applied.
+    pub fn reapply(&self) -> Result<Vec<String>, Box<dyn Error + Send + Sync>> {
+        let mut conn = self.connect();
+        let applied = conn.run_pending_migrations(self.migrations.clone())?;
+        Ok(applied.iter().map(|version| version.to_string()).collect())
+    }
+
+    /// Bring the database to exactly the migration `version`, e.g.
+    /// `20221208031140` or `2022-12-08-031140`: newer migrations are reverted
+    /// newest first, older pending ones applied. To test a data migration,
+    /// go back to the version before it, insert rows in the old shape, then
+    /// move to the migration itself and check what it made of them.
+    ///
+    /// ```no_run
+    /// # use diesel_database_tester::TestDb;
+    /// let tdb = TestDb::builder().build();
+    /// tdb.run_migrations_to("20221201000000").unwrap();
+    /// // ... insert legacy rows ...
+    /// tdb.run_migrations_to("20221208031140").unwrap();
+    /// // ... assert they were transformed ...
+    /// ```
+    pub fn run_migrations_to(&self, version: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let target: String = version.chars().filter(char::is_ascii_digit).collect();
+        let migrations = self.migrations.migrations()?;
+        if !migrations
+            .iter()
+            .any(|migration| migration.name().version().to_string() == target)
+        {
+            return Err(format!("No migration with version {}", version).into());
+        }
+
+        let mut conn = self.connect();
+        // versions are timestamps of equal length, so they sort as strings
+        while let Some(latest) = conn
+            .applied_migrations()?
+            .into_iter()
+            .map(|applied| applied.to_string())
+            .max()
+            .filter(|latest| *latest > target)
+        {
+            diagnostics::log(format_args!("reverting migration {}", latest));
+            conn.revert_last_migration(self.migrations.clone())?;
+        }
+        for migration in conn.pending_migrations(self.migrations.clone())? {
+            if migration.name().version().to_string() <= target {
+                diagnostics::log(format_args!("applying migration {}", migration.name()));
+                conn.run_migration(&*migration)?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,65 @@
+//! Round-trip checks for custom `ToSql`/`FromSql` type mappings.
+
+use std::fmt::Debug;
+
+use diesel::{
+    connection::SimpleConnection,
+    deserialize::FromSqlRow,
+    dsl::sql,
+    expression::TypedExpressionType,
+    pg::Pg,
+    query_builder::QueryId,
+    select,
+    serialize::ToSql,
+    sql_query,
+    sql_types::{HasSqlType, SingleValue, SqlType},
+    PgConnection, QueryResult, RunQueryDsl,
+};
+
+use crate::TestDb;
+
+/// Write `value` into a temporary table with a single column of type
+/// `sql_type`, e.g. `"my_enum"` or `"numeric(10, 2)"`, and read it back.
+pub fn round_trip<ST, T>(conn: &mut PgConnection, sql_type: &str, value: &T) -> QueryResult<T>
+where
+    ST: SqlType + SingleValue + TypedExpressionType + QueryId + 'static,
+    Pg: HasSqlType<ST>,
+    T: ToSql<ST, Pg> + FromSqlRow<ST, Pg> + 'static,
+{
+    conn.batch_execute(&format!(
+        "DROP TABLE IF EXISTS pg_temp.round_trip; CREATE TEMP TABLE round_trip (value {})",
+        sql_type
+    ))?;
+    sql_query("INSERT INTO pg_temp.round_trip (value) VALUES ($1)")
+        .bind::<ST, _>(value)
+        .execute(conn)?;
+    select(sql::<ST>("(SELECT value FROM pg_temp.round_trip)")).get_result(conn)
+}
+
+impl TestDb {
+    /// Assert that `value` comes back unchanged after being written to and
+    /// read from a column of type `sql_type`, exercising the `ToSql` and
+    /// `FromSql` implementations of a custom type mapping.
+    ///
+    /// ```no_run
+    /// # use diesel_database_tester::TestDb;
+    /// # let tdb = TestDb::builder().build();
+    /// use diesel::sql_types::Text;
+    ///
+    /// tdb.assert_round_trip::<Text, _>("text", "héllo".to_string());
+    /// ```
+    pub fn assert_round_trip<ST, T>(&self, sql_type: &str, value: T)
+    where
+        ST: SqlType + SingleValue + TypedExpressionType + QueryId + 'static,
+        Pg: HasSqlType<ST>,
+        T: ToSql<ST, Pg> + FromSqlRow<ST, Pg> + PartialEq + Debug + 'static,
+    {
+        let read = round_trip::<ST, T>(&mut self.connect(), sql_type, &value)
+            .unwrap_or_else(|e| panic!("Failed to round-trip {:?} as {}: {}", value, sql_type, e));
+        assert_eq!(
+            read, value,
+            "value changed when round-tripped as {}",
+            sql_type
+        );
+    }
+}
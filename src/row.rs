@@ -0,0 +1,94 @@
+//! Generated rows and the SQL inserting them.
+
+use chrono::{NaiveDate, NaiveDateTime};
+use diesel::{PgConnection, QueryResult};
+use uuid::Uuid;
+
+use crate::{diagnostics, sql::quote_ident};
+
+/// A single generated column value.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    /// A `numeric` value already rendered with the column's scale.
+    Numeric(String),
+    /// Text, and enum labels.
+    Text(String),
+    Date(NaiveDate),
+    Timestamp(NaiveDateTime),
+    Uuid(Uuid),
+}
+
+impl Value {
+    /// A `numeric` value from an integer count of `10^-scale` units.
+    pub(crate) fn numeric(units: i64, scale: u32) -> Value {
+        let digits = format!(
+            "{:0width$}",
+            units.unsigned_abs(),
+            width = scale as usize + 1
+        );
+        let (int, frac) = digits.split_at(digits.len() - scale as usize);
+        let sign = if units < 0 { "-" } else { "" };
+        Value::Numeric(if scale == 0 {
+            format!("{}{}", sign, int)
+        } else {
+            format!("{}{}.{}", sign, int, frac)
+        })
+    }
+
+    /// The value as an SQL literal, e.g. `'it''s'` or `NULL`.
+    pub fn to_sql(&self) -> String {
+        match self {
+            Value::Null => "NULL".into(),
+            Value::Bool(b) => b.to_string().to_uppercase(),
+            Value::Int(i) => i.to_string(),
+            Value::Float(f) => format!("'{:?}'", f),
+            Value::Numeric(n) => n.clone(),
+            Value::Text(s) => format!("'{}'", s.replace('\'', "''")),
+            Value::Date(d) => format!("'{}'", d),
+            Value::Timestamp(ts) => format!("'{}'", ts),
+            Value::Uuid(u) => format!("'{}'", u),
+        }
+    }
+}
+
+/// A generated row of `table`. Columns filled by the server (serial,
+/// identity, generated) are left out.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Row {
+    pub table: String,
+    pub values: Vec<(String, Value)>,
+}
+
+impl Row {
+    pub fn get(&self, column: &str) -> Option<&Value> {
+        self.values
+            .iter()
+            .find(|(name, _)| name == column)
+            .map(|(_, value)| value)
+    }
+
+    pub fn insert_sql(&self) -> String {
+        if self.values.is_empty() {
+            return format!("INSERT INTO {} DEFAULT VALUES", quote_ident(&self.table));
+        }
+        let (columns, values): (Vec<_>, Vec<_>) = self
+            .values
+            .iter()
+            .map(|(name, value)| (quote_ident(name), value.to_sql()))
+            .unzip();
+        format!(
+            "INSERT INTO {} ({}) VALUES ({})",
+            quote_ident(&self.table),
+            columns.join(", "),
+            values.join(", ")
+        )
+    }
+
+    pub fn insert(&self, conn: &mut PgConnection) -> QueryResult<usize> {
+        diagnostics::execute(conn, &self.insert_sql())
+    }
+}
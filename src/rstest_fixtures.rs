@@ -0,0 +1,101 @@
+//! Ready-made [`rstest`](https://docs.rs/rstest) fixtures, enabled with the
+//! `rstest` feature.
+//!
+//! Every fixture builds its database with [`TestDbBuilder::from_env`] unless
+//! a test passes a builder of its own with `#[with(...)]`:
+//!
+//! ```no_run
+//! use diesel_database_tester::{rstest_fixtures::*, TestDb, TestDbBuilder};
+//! use rstest::rstest;
+//!
+//! #[rstest]
+//! fn lists_todos(pool: TestPool) {
+//!     let mut conn = pool.get().unwrap();
+//!     // ...
+//! }
+//!
+//! #[rstest]
+//! fn with_fixtures(
+//!     #[with(TestDb::builder().with_fixtures(["fixtures/todos.sql"]))] test_db: TestDb,
+//! ) {
+//!     // ...
+//! }
+//! ```
+
+use std::ops::Deref;
+
+use rstest::fixture;
+
+use crate::{Pool, TestDb, TestDbBuilder};
+
+/// A pool that keeps its test database alive, dereferencing to the [`Pool`].
+pub struct TestPool {
+    // dropped first, so the database isn't dropped under open connections
+    pool: Pool,
+    test_db: TestDb,
+}
+
+impl TestPool {
+    pub fn test_db(&self) -> &TestDb {
+        &self.test_db
+    }
+}
+
+impl Deref for TestPool {
+    type Target = Pool;
+
+    fn deref(&self) -> &Pool {
+        &self.pool
+    }
+}
+
+/// A migrated test database of its own for every test.
+#[fixture]
+pub fn test_db(#[default(TestDbBuilder::from_env())] builder: TestDbBuilder) -> TestDb {
+    builder.build()
+}
+
+/// A pool to a test database of its own for every test.
+#[fixture]
+pub fn pool(#[default(TestDbBuilder::from_env())] builder: TestDbBuilder) -> TestPool {
+    let test_db = builder.build();
+    TestPool {
+        pool: test_db.pool(),
+        test_db,
+    }
+}
+
+/// Like [`test_db`], without blocking the runtime of an async test. Take it
+/// with `#[future(awt)]`, or `#[future]` and `.await` it.
+#[fixture]
+pub async fn async_test_db(#[default(TestDbBuilder::from_env())] builder: TestDbBuilder) -> TestDb {
+    builder.build_async().await
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+    use crate::count_rows;
+
+    #[rstest]
+    fn pool_should_keep_its_database_alive(
+        #[with(TestDb::builder().port(15432).password("7cOPpA7dnc"))] pool: TestPool,
+    ) {
+        let mut conn = pool.get().unwrap();
+        assert_eq!(count_rows(&mut conn, "todos").unwrap(), 0);
+        assert!(pool.test_db().dbname.starts_with("test_"));
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn async_test_db_should_be_migrated(
+        #[future(awt)]
+        #[with(TestDb::builder().port(15432).password("7cOPpA7dnc"))]
+        async_test_db: TestDb,
+    ) {
+        let mut conn = async_test_db.connect();
+        assert_eq!(count_rows(&mut conn, "todos").unwrap(), 0);
+    }
+}
@@ -0,0 +1,100 @@
+//! On-disk cache of the migrated schema, shared by every test binary.
+//!
+//! The first database migrated with a cache directory configured is dumped
+//! with `pg_dump`, rows inserted by the migrations included, into
+//! `schema-<hash>.sql`, where the hash
+//! covers the names and SQL of all migrations and the statements run before
+//! them (custom types). Later databases restore that file instead of running
+//! the migrations, until a migration is added, renamed or edited. When
+//! `pg_dump` isn't available the migrations simply run as usual.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use diesel::{connection::SimpleConnection, PgConnection, QueryResult};
+use log::warn;
+
+use crate::{diagnostics, migrations::MigrationSet};
+
+/// Where the dump of the current migrations lives in `dir`.
+pub(crate) fn path(dir: &Path, migrations: &MigrationSet, setup_sql: &[String]) -> PathBuf {
+    dir.join(format!(
+        "schema-{:016x}.sql",
+        setup_hash(migrations, setup_sql)
+    ))
+}
+
+/// FNV-1a over the migrations and the statements run before them, stable
+/// across toolchains and processes.
+pub(crate) fn setup_hash(migrations: &MigrationSet, setup_sql: &[String]) -> u64 {
+    stable_hash(migrations.fingerprint().iter().chain(setup_sql))
+}
+
+/// FNV-1a over `parts`, each terminated by a NUL byte.
+pub(crate) fn stable_hash<S: AsRef<str>>(parts: impl IntoIterator<Item = S>) -> u64 {
+    let mut hash = 0xcbf2_9ce4_8422_2325;
+    for part in parts {
+        for b in part.as_ref().bytes().chain([0]) {
+            hash = (hash ^ b as u64).wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+    hash
+}
+
+/// Restore the cached schema into the freshly created database, if cached.
+pub(crate) fn restore(conn: &mut PgConnection, path: &Path) -> Option<QueryResult<()>> {
+    let dump = fs::read_to_string(path).ok()?;
+    diagnostics::log(format_args!("restoring schema from {}", path.display()));
+    Some(conn.batch_execute(&dump).and_then(|_| {
+        // the dump leaves an empty search_path and other settings behind
+        conn.batch_execute("RESET ALL")
+    }))
+}
+
+/// Dump the migrated database at `url` into the cache, rows included: lookup
+/// and seed data inserted by migrations belong to the migrated state as much
+/// as the schema and `__diesel_schema_migrations` do.
+pub(crate) fn store(url: &str, path: &Path) {
+    let output = Command::new("pg_dump")
+        // plain INSERTs, `COPY ... FROM stdin` can't be replayed through batch_execute
+        .args(["--inserts", "--no-owner", "--no-privileges", "--dbname"])
+        .arg(url)
+        .output();
+    let dump = match output {
+        Ok(output) if output.status.success() => String::from_utf8_lossy(&output.stdout)
+            .lines()
+            // psql meta-commands such as `\restrict` can't be executed by the server
+            .filter(|line| !line.starts_with('\\'))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        Ok(output) => {
+            warn!(
+                "Not caching the schema, pg_dump failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+            return;
+        }
+        Err(e) => {
+            warn!("Not caching the schema, failed to run pg_dump: {}", e);
+            return;
+        }
+    };
+
+    // write to a temporary file first, other test binaries may be reading
+    let tmp = path.with_extension(format!("tmp{}", std::process::id()));
+    let result = path
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|_| fs::write(&tmp, dump))
+        .and_then(|_| fs::rename(&tmp, path));
+    match result {
+        Ok(()) => diagnostics::log(format_args!("cached schema in {}", path.display())),
+        Err(e) => {
+            let _ = fs::remove_file(&tmp);
+            warn!("Failed to write schema cache {}: {}", path.display(), e);
+        }
+    }
+}
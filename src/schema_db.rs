@@ -0,0 +1,167 @@
+//! Isolation by schema instead of by database, for servers where the test
+//! user can't `CREATE DATABASE`, e.g. restricted roles on managed services.
+//!
+//! Every test gets a uniquely named schema in an existing database. The
+//! migrations run into it and every connection handed out puts it first on
+//! its `search_path`, so unqualified names resolve to the test's own tables.
+//! The schema is dropped with everything in it when the test is done.
+
+use std::{error::Error, time::Duration};
+
+use diesel::{r2d2::ManageConnection, PgConnection};
+use log::{error, info};
+
+use crate::{
+    connect_to,
+    connection::{redact_url, ConnectionConfig},
+    diagnostics, retry, run_migrations, sql, Pool, TestDbBuilder, TestDbConnectionManager,
+    TestDbError,
+};
+
+/// A schema of its own in a shared database, dropped on drop.
+///
+/// Tests that qualify names with `public.`, or need database level settings
+/// or extensions of their own, still need a [`TestDb`](crate::TestDb).
+pub struct SchemaTestDb {
+    config: ConnectionConfig,
+    database: String,
+    schema: String,
+    pool_size: Option<u32>,
+    pool_timeout: Option<Duration>,
+}
+
+impl TestDbBuilder {
+    /// Create a schema in the existing `database` and run the migrations into
+    /// it, instead of creating a database, see [`SchemaTestDb`].
+    ///
+    /// The custom types are created in the schema as well; the profile's
+    /// roles and the extensions need privileges this mode is meant to avoid,
+    /// so they are expected to exist already.
+    ///
+    /// ```no_run
+    /// use diesel_database_tester::TestDb;
+    ///
+    /// let tdb = TestDb::builder().build_schema("app");
+    /// let pool = tdb.pool();
+    /// // unqualified `todos` is the test schema's own table
+    /// ```
+    pub fn build_schema(self, database: impl Into<String>) -> SchemaTestDb {
+        self.try_build_schema(database)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Like [`build_schema`](Self::build_schema), returning the error instead
+    /// of panicking.
+    pub fn try_build_schema(
+        self,
+        database: impl Into<String>,
+    ) -> Result<SchemaTestDb, TestDbError> {
+        let database = database.into();
+        let schema = self.try_database_name()?;
+        let mut config = self.try_connection_config()?;
+        config.session_sql.insert(0, sql::set_search_path(&schema));
+        let url = config
+            .database_url(&database)
+            .map_err(|e| TestDbError::Config(e.to_string()))?;
+        diagnostics::log(format_args!(
+            "creating schema {} in {}",
+            schema,
+            redact_url(&url)
+        ));
+        info!("Creating test schema {} in {}", schema, database);
+        let mut conn = diagnostics::phase("connect to database", || {
+            retry::connect(&url, self.connect_retry.as_ref())
+        })?;
+        diagnostics::execute(&mut conn, &sql::create_schema(&schema))
+            .map_err(TestDbError::CreateDatabase)?;
+        // from here on the schema exists and is dropped again by `Drop` on failure
+        let tdb = SchemaTestDb {
+            config,
+            database,
+            schema,
+            pool_size: self.pool_size,
+            pool_timeout: self.pool_timeout,
+        };
+        let setup_failed = |phase, source: Box<dyn Error + Send + Sync>| TestDbError::Setup {
+            dbname: tdb.schema.clone(),
+            phase,
+            source,
+        };
+        diagnostics::execute(&mut conn, &sql::set_search_path(&tdb.schema))
+            .map_err(|e| setup_failed("set the search path", e.into()))?;
+        for statement in &self.types {
+            diagnostics::execute(&mut conn, statement)
+                .map_err(|e| setup_failed("create custom types", e.into()))?;
+        }
+        diagnostics::phase("run migrations", || {
+            run_migrations(&mut conn, &self.migrations, &|_| {})
+        })
+        .map_err(|e| setup_failed("run migrations", e))?;
+        Ok(tdb)
+    }
+}
+
+impl SchemaTestDb {
+    /// The name of the test schema.
+    pub fn schema(&self) -> &str {
+        &self.schema
+    }
+
+    /// The name of the database the schema lives in.
+    pub fn database(&self) -> &str {
+        &self.database
+    }
+
+    /// Url of the database holding the schema. Connections opened with it
+    /// directly don't have the test schema on their `search_path`.
+    pub fn url(&self) -> String {
+        self.config
+            .database_url(&self.database)
+            .unwrap_or_else(|e| panic!("Failed to resolve connection endpoint: {}", e))
+    }
+
+    /// A pool whose connections all resolve unqualified names in the test
+    /// schema.
+    pub fn pool(&self) -> Pool {
+        self.try_pool().unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Like [`pool`](Self::pool), returning the error instead of panicking.
+    pub fn try_pool(&self) -> Result<Pool, TestDbError> {
+        let mut builder = Pool::builder();
+        if let Some(size) = self.pool_size {
+            builder = builder.max_size(size);
+        }
+        if let Some(timeout) = self.pool_timeout {
+            builder = builder.connection_timeout(timeout);
+        }
+        builder.build(self.manager()).map_err(TestDbError::Pool)
+    }
+
+    /// A new connection with the test schema on its `search_path`.
+    pub fn connect(&self) -> PgConnection {
+        self.manager()
+            .connect()
+            .unwrap_or_else(|e| panic!("Error connecting to {}: {}", self.database, e))
+    }
+
+    fn manager(&self) -> TestDbConnectionManager {
+        TestDbConnectionManager::new(self.config.clone(), &self.database)
+    }
+}
+
+impl Drop for SchemaTestDb {
+    fn drop(&mut self) {
+        info!("Dropping test schema {} in {}", self.schema, self.database);
+        let dropped = connect_to(&self.url())
+            .map_err(|e| e.to_string())
+            .and_then(|mut conn| {
+                diagnostics::execute(&mut conn, &sql::drop_schema(&self.schema))
+                    .map_err(|e| e.to_string())
+            });
+        if let Err(e) = dropped {
+            // best effort, panicking here would abort a test that is already failing
+            error!("Error while dropping schema {}: {}", self.schema, e);
+        }
+    }
+}
@@ -0,0 +1,82 @@
+//! Connection service file (`pg_service.conf`) support.
+//!
+//! See <https://www.postgresql.org/docs/current/libpq-pgservice.html>. The
+//! per-user file (`$PGSERVICEFILE` or `~/.pg_service.conf`) is consulted
+//! first, then the system-wide `pg_service.conf` in `$PGSYSCONFDIR`.
+
+use std::{collections::HashMap, env, fs, path::PathBuf};
+
+/// Connection parameters of a single service definition.
+pub(crate) type ServiceParams = HashMap<String, String>;
+
+/// Find the definition of `service` in the service files, like libpq does.
+pub(crate) fn lookup(service: &str) -> Option<ServiceParams> {
+    service_files()
+        .into_iter()
+        .filter_map(|path| fs::read_to_string(path).ok())
+        .find_map(|contents| find_service(&contents, service))
+}
+
+fn service_files() -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    if let Some(path) = env::var_os("PGSERVICEFILE") {
+        files.push(path.into());
+    } else if let Some(home) = env::var_os("HOME") {
+        files.push(PathBuf::from(home).join(".pg_service.conf"));
+    }
+    let sysconfdir = env::var_os("PGSYSCONFDIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("/etc/postgresql-common"));
+    files.push(sysconfdir.join("pg_service.conf"));
+    files
+}
+
+fn find_service(contents: &str, service: &str) -> Option<ServiceParams> {
+    let mut params: Option<ServiceParams> = None;
+    for line in contents.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some(section) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            if params.is_some() {
+                break;
+            }
+            if section.trim() == service {
+                params = Some(ServiceParams::new());
+            }
+        } else if let (Some(params), Some((key, value))) = (params.as_mut(), line.split_once('=')) {
+            params.insert(key.trim().to_string(), value.trim().to_string());
+        }
+    }
+    params
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn service_definitions_should_be_parsed() {
+        let contents = r#"
+# shared definitions
+[local]
+host=localhost
+
+[ci-postgres]
+host = db.ci.internal
+port=6543
+user=ci
+sslmode=require
+
+[other]
+host=elsewhere
+"#;
+        let params = find_service(contents, "ci-postgres").unwrap();
+        assert_eq!(params["host"], "db.ci.internal");
+        assert_eq!(params["port"], "6543");
+        assert_eq!(params["user"], "ci");
+        assert_eq!(params["sslmode"], "require");
+        assert_eq!(params.len(), 4);
+        assert!(find_service(contents, "missing").is_none());
+    }
+}
@@ -0,0 +1,98 @@
+//! Detection of connections still open to a test database at teardown.
+
+use std::{
+    sync::{Mutex, PoisonError},
+    thread,
+    time::Duration,
+};
+
+use diesel::{
+    sql_query,
+    sql_types::{Integer, Nullable, Text},
+    PgConnection, QueryResult, QueryableByName, RunQueryDsl,
+};
+use log::warn;
+
+/// How long closed clients get to disappear from `pg_stat_activity`, since the
+/// server notices a closed socket asynchronously.
+const GRACE: Duration = Duration::from_millis(200);
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// A connection that was still open to a test database when it was dropped,
+/// e.g. from a pool or client that was never shut down.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LeakedSession {
+    pub database: String,
+    /// The test that owned the database, when known.
+    pub test: Option<String>,
+    pub pid: i32,
+    pub application_name: String,
+    /// `active`, `idle`, `idle in transaction`, ...
+    pub state: Option<String>,
+    /// The last query the session ran.
+    pub query: String,
+}
+
+static LEAKED: Mutex<Vec<LeakedSession>> = Mutex::new(Vec::new());
+
+#[derive(QueryableByName)]
+struct Session {
+    #[diesel(sql_type = Integer)]
+    pid: i32,
+    #[diesel(sql_type = Text)]
+    application_name: String,
+    #[diesel(sql_type = Nullable<Text>)]
+    state: Option<String>,
+    #[diesel(sql_type = Text)]
+    query: String,
+}
+
+fn sessions(conn: &mut PgConnection, dbname: &str) -> QueryResult<Vec<Session>> {
+    sql_query(
+        "SELECT pid, COALESCE(application_name, '') AS application_name, state, \
+         COALESCE(query, '') AS query FROM pg_stat_activity \
+         WHERE datname = $1 AND backend_type = 'client backend' AND pid <> pg_backend_pid() \
+         ORDER BY pid",
+    )
+    .bind::<Text, _>(dbname)
+    .load(conn)
+}
+
+/// Record and log the client sessions still connected to `dbname`, just
+/// before they are terminated to drop it.
+pub(crate) fn check(conn: &mut PgConnection, dbname: &str, test: Option<&str>) -> QueryResult<()> {
+    let mut open = sessions(conn, dbname)?;
+    let mut waited = Duration::ZERO;
+    while !open.is_empty() && waited < GRACE {
+        thread::sleep(POLL_INTERVAL);
+        waited += POLL_INTERVAL;
+        open = sessions(conn, dbname)?;
+    }
+    let mut leaked = LEAKED.lock().unwrap_or_else(PoisonError::into_inner);
+    for session in open {
+        warn!(
+            "{} left a connection open to {}: pid {}, application_name {:?}, state {:?}, query {:?}",
+            test.unwrap_or("a test"),
+            dbname,
+            session.pid,
+            session.application_name,
+            session.state,
+            session.query
+        );
+        leaked.push(LeakedSession {
+            database: dbname.to_string(),
+            test: test.map(str::to_string),
+            pid: session.pid,
+            application_name: session.application_name,
+            state: session.state,
+            query: session.query,
+        });
+    }
+    Ok(())
+}
+
+/// Every connection found still open to a test database when it was
+/// dropped, so far in this run.
+pub fn leaked_sessions() -> Vec<LeakedSession> {
+    LEAKED.lock().unwrap().clone()
+}
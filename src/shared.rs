@@ -0,0 +1,324 @@
+//! Databases shared by the tests of a process, each test isolated in a
+//! transaction that is never committed, for unit tests too cheap to pay for a
+//! database of their own.
+//!
+//! One database is created per distinct builder configuration on first use
+//! and dropped when the process exits. Closures given to the builder (seeds,
+//! hooks, credential providers, ...) can't be compared, so builders only
+//! share a database when theirs are the very same closures, i.e. when they
+//! are clones of one builder. [`SharedTestDb`] does the same for a
+//! database declared once in a `static`, handing out pools instead.
+
+use std::{
+    collections::HashMap,
+    error::Error,
+    fmt::Write,
+    mem,
+    sync::{Arc, Mutex, Once, OnceLock, PoisonError},
+};
+
+use diesel::{Connection, PgConnection};
+use log::error;
+
+use crate::{drop_database, Pool, TestDb, TestDbBuilder, TestDbError};
+
+/// The shared databases by [`sharing_key`], each with the builder it was
+/// built from, which keeps the closures the key points to alive.
+static SHARED: OnceLock<Mutex<HashMap<String, (TestDbBuilder, TestDb)>>> = OnceLock::new();
+
+fn shared() -> &'static Mutex<HashMap<String, (TestDbBuilder, TestDb)>> {
+    SHARED.get_or_init(Default::default)
+}
+
+/// What decides whether two builders share a database: every setting by
+/// value, the password included, except the label, which only names the
+/// database; and every closure by the address of its allocation.
+fn sharing_key(builder: &TestDbBuilder) -> String {
+    // destructured so a new setting can't be left out by accident
+    let TestDbBuilder {
+        host,
+        port,
+        user,
+        password,
+        sslmode,
+        params,
+        service,
+        credentials,
+        refresh,
+        transport,
+        naming,
+        name_with,
+        label: _,
+        analyze_after_seed,
+        database_settings,
+        random_seed,
+        schema_cache,
+        shared_template,
+        // only observes the setup
+        on_migration: _,
+        extensions,
+        types,
+        database_options,
+        minimum_version,
+        connect_retry,
+        app_role,
+        quota,
+        profile,
+        migrations,
+        prefix,
+        pool_size,
+        pool_timeout,
+        pool_min_idle,
+        pool_idle_timeout,
+        fixtures,
+        session_sql,
+        trace_sql,
+        drop_timeout,
+        seeds,
+        before_migrations,
+        after_migrations,
+        container,
+        container_tag,
+        embedded,
+        replica,
+    } = builder;
+    let mut key = format!(
+        "{:?}",
+        (
+            (host, port, user, password, sslmode, params, service),
+            (naming, analyze_after_seed, database_settings, random_seed),
+            (schema_cache, shared_template, extensions, types),
+            (database_options, minimum_version, connect_retry, app_role),
+            (quota, profile, migrations.fingerprint(), prefix),
+            (pool_size, pool_timeout, pool_min_idle, pool_idle_timeout),
+            (fixtures, session_sql, trace_sql, drop_timeout),
+            (container, container_tag, embedded, replica),
+        )
+    );
+    let closures = [
+        credentials.as_ref().map(address),
+        refresh.as_ref().map(address),
+        transport.as_ref().map(address),
+        name_with.as_ref().map(address),
+    ]
+    .into_iter()
+    .flatten()
+    .chain(seeds.iter().map(address))
+    .chain(before_migrations.iter().map(address))
+    .chain(after_migrations.iter().map(address));
+    for closure in closures {
+        write!(key, " {:#x}", closure).unwrap();
+    }
+    key
+}
+
+fn address<T: ?Sized>(closure: &Arc<T>) -> usize {
+    Arc::as_ptr(closure) as *const () as usize
+}
+
+/// Drop the shared databases when the process exits. Registered once the
+/// first one exists, so this runs before the exit handlers of the libraries
+/// its connections use.
+fn drop_on_exit() {
+    static REGISTER: Once = Once::new();
+    extern "C" fn drop_all() {
+        if let Ok(mut shared) = shared().try_lock() {
+            shared.drain().for_each(|(_, (_, tdb))| drop_at_exit(tdb));
+        }
+        if let Ok(instances) = INSTANCES.try_lock() {
+            for instance in instances.iter() {
+                if let Ok(mut state) = instance.state.try_lock() {
+                    if let Some((tdb, pool)) = state.take() {
+                        mem::forget(pool);
+                        drop_at_exit(tdb);
+                    }
+                }
+            }
+        }
+    }
+    REGISTER.call_once(|| unsafe {
+        libc::atexit(drop_all);
+    });
+}
+
+/// Past the end of `main`, drop directly rather than through `Drop`, which
+/// may queue on a thread or look at the current test. The server it ran on
+/// still stops with its last database.
+#[cfg_attr(
+    not(any(feature = "testcontainers", feature = "embedded-pg")),
+    allow(unused_mut)
+)]
+fn drop_at_exit(mut tdb: TestDb) {
+    if let Err(e) = drop_database(&tdb.server_url(), &tdb.dbname, None, tdb.drop_timeout) {
+        error!("Error while dropping database {}: {}", tdb.dbname, e);
+    }
+    #[cfg(feature = "testcontainers")]
+    let container = tdb.container.take();
+    #[cfg(feature = "embedded-pg")]
+    let embedded = tdb.embedded.take();
+    mem::forget(tdb);
+    #[cfg(feature = "testcontainers")]
+    drop(container);
+    #[cfg(feature = "embedded-pg")]
+    drop(embedded);
+}
+
+impl TestDbBuilder {
+    /// A connection to the database shared by every test using a builder
+    /// configured like this one, inside a test transaction: whatever the
+    /// test writes is rolled back when the connection is dropped and never
+    /// seen by other tests. Builders with seeds, hooks or other closures
+    /// only share with clones of themselves.
+    ///
+    /// The database is created and migrated by the first test asking for it.
+    /// Tests needing a second connection, `COMMIT`s or database level changes
+    /// still need a database of their own from [`build`](Self::build).
+    ///
+    /// ```no_run
+    /// use diesel::prelude::*;
+    /// use diesel_database_tester::TestDb;
+    ///
+    /// let mut conn = TestDb::builder().test_transaction();
+    /// diesel::sql_query("INSERT INTO todos (title) VALUES ('gone')")
+    ///     .execute(&mut conn)
+    ///     .unwrap();
+    /// ```
+    pub fn test_transaction(self) -> PgConnection {
+        self.try_test_transaction()
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Like [`test_transaction`](Self::test_transaction), returning the error
+    /// instead of panicking.
+    pub fn try_test_transaction(self) -> Result<PgConnection, TestDbError> {
+        let key = sharing_key(&self);
+        let mut shared = shared().lock().unwrap_or_else(PoisonError::into_inner);
+        if !shared.contains_key(&key) {
+            let tdb = self.clone().named("shared").try_build()?;
+            shared.insert(key.clone(), (self, tdb));
+            drop_on_exit();
+        }
+        let (_, tdb) = &shared[&key];
+        let mut conn = tdb.connect();
+        conn.begin_test_transaction()
+            .map_err(|e| TestDbError::Setup {
+                dbname: tdb.dbname.clone(),
+                phase: "begin the test transaction",
+                source: e.into(),
+            })?;
+        Ok(conn)
+    }
+}
+
+impl TestDb {
+    /// A connection in a test transaction on a database shared with the other
+    /// tests, see [`TestDbBuilder::test_transaction`].
+    pub fn test_transaction() -> PgConnection {
+        TestDbBuilder::new().test_transaction()
+    }
+}
+
+/// The [`SharedTestDb`]s that have a database, dropped at exit.
+static INSTANCES: Mutex<Vec<&'static SharedTestDb>> = Mutex::new(Vec::new());
+
+/// One database for a whole test binary: created and migrated the first time
+/// any test asks for it, then shared by all of them until the process exits
+/// or [`teardown`](Self::teardown) is called.
+///
+/// Tests see each other's committed rows; isolate them with
+/// [`test_transaction`](Self::test_transaction) or start them with
+/// [`reset`](Self::reset).
+///
+/// ```no_run
+/// use diesel_database_tester::{SharedTestDb, TestDb};
+///
+/// static DB: SharedTestDb = SharedTestDb::new(|| TestDb::builder().migrations_dir("migrations"));
+///
+/// #[test]
+/// fn lists_todos() {
+///     let mut conn = DB.pool().get().unwrap();
+///     // ...
+/// }
+/// ```
+pub struct SharedTestDb {
+    configure: fn() -> TestDbBuilder,
+    state: Mutex<Option<(TestDb, Pool)>>,
+}
+
+impl SharedTestDb {
+    /// A shared database built by the builder `configure` returns.
+    pub const fn new(configure: fn() -> TestDbBuilder) -> Self {
+        SharedTestDb {
+            configure,
+            state: Mutex::new(None),
+        }
+    }
+
+    /// The pool to the shared database, the same one for every test.
+    pub fn pool(&'static self) -> Pool {
+        self.try_pool().unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Like [`pool`](Self::pool), returning the error instead of panicking.
+    pub fn try_pool(&'static self) -> Result<Pool, TestDbError> {
+        self.with(|_, pool| pool.clone())
+    }
+
+    /// Url of the shared database.
+    pub fn url(&'static self) -> String {
+        self.with(|tdb, _| tdb.url())
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// A connection in a transaction that is never committed, so whatever the
+    /// test writes is rolled back when the connection is dropped.
+    pub fn test_transaction(&'static self) -> PgConnection {
+        let mut conn = self
+            .with(|tdb, _| tdb.connect())
+            .unwrap_or_else(|e| panic!("{}", e));
+        conn.begin_test_transaction()
+            .unwrap_or_else(|e| panic!("Failed to begin the test transaction: {}", e));
+        conn
+    }
+
+    /// Empty the shared database and load its fixtures again, see
+    /// [`TestDb::reset`].
+    pub fn reset(&'static self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        self.with(|tdb, _| tdb.reset())?
+    }
+
+    /// Drop the shared database now rather than at exit. The next test asking
+    /// for it gets a new one.
+    pub fn teardown(&'static self) {
+        let taken = self
+            .state
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take();
+        if let Some((tdb, pool)) = taken {
+            // close the pool's connections first, they'd be reported as leaked
+            drop(pool);
+            drop(tdb);
+        }
+    }
+
+    /// Run `f` on the database, creating it first if needed.
+    fn with<T>(&'static self, f: impl FnOnce(&TestDb, &Pool) -> T) -> Result<T, TestDbError> {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        if state.is_none() {
+            let tdb = (self.configure)().named("shared").try_build()?;
+            let pool = tdb.try_pool()?;
+            *state = Some((tdb, pool));
+            let mut instances = INSTANCES.lock().unwrap_or_else(PoisonError::into_inner);
+            if !instances
+                .iter()
+                .any(|instance| std::ptr::eq(*instance, self))
+            {
+                instances.push(self);
+            }
+            drop_on_exit();
+        }
+        let (tdb, pool) = state.as_ref().unwrap();
+        Ok(f(tdb, pool))
+    }
+}
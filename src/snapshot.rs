@@ -0,0 +1,95 @@
+//! Snapshot assertions on the SQL diesel generates, caught without hitting
+//! the database.
+//!
+//! Snapshots live in `snapshots/sql/<name>.sql` under the crate being tested
+//! (or `$TESTDB_SNAPSHOT_DIR`). Missing snapshots are written on first run;
+//! set `TESTDB_UPDATE_SNAPSHOTS=1` to overwrite changed ones.
+
+use std::{env, fs, path::PathBuf};
+
+use diesel::{debug_query, pg::Pg, query_builder::QueryFragment};
+
+/// Render `query` with [`debug_query`] and collapse whitespace, so snapshots
+/// don't depend on formatting details.
+pub fn normalized_sql<Q: QueryFragment<Pg>>(query: &Q) -> String {
+    normalize(&debug_query::<Pg, _>(query).to_string())
+}
+
+fn normalize(sql: &str) -> String {
+    sql.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Assert that `query` renders to `expected`, ignoring whitespace differences.
+pub fn assert_sql_eq<Q: QueryFragment<Pg>>(query: &Q, expected: &str) {
+    assert_eq!(normalized_sql(query), normalize(expected));
+}
+
+/// Compare the SQL of `query` with the snapshot called `name`.
+pub fn assert_sql_snapshot<Q: QueryFragment<Pg>>(name: &str, query: &Q) {
+    assert_sql_snapshot_in(snapshot_dir(), name, query)
+}
+
+/// Like [`assert_sql_snapshot`], storing snapshots in `dir`.
+pub fn assert_sql_snapshot_in<Q: QueryFragment<Pg>>(
+    dir: impl Into<PathBuf>,
+    name: &str,
+    query: &Q,
+) {
+    let path = dir.into().join(format!("{}.sql", name));
+    let actual = normalized_sql(query);
+    let update = env::var("TESTDB_UPDATE_SNAPSHOTS")
+        .map(|v| v == "1")
+        .unwrap_or(false);
+    match fs::read_to_string(&path) {
+        Ok(expected) if !update => assert_eq!(
+            actual,
+            normalize(&expected),
+            "SQL of {} changed, rerun with TESTDB_UPDATE_SNAPSHOTS=1 to accept it",
+            name
+        ),
+        _ => {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent).expect("Failed to create snapshot directory");
+            }
+            fs::write(&path, format!("{}\n", actual)).expect("Failed to write snapshot");
+        }
+    }
+}
+
+fn snapshot_dir() -> PathBuf {
+    if let Some(dir) = env::var_os("TESTDB_SNAPSHOT_DIR") {
+        return dir.into();
+    }
+    env::var_os("CARGO_MANIFEST_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_default()
+        .join("snapshots/sql")
+}
+
+#[cfg(test)]
+mod tests {
+    use diesel::prelude::*;
+
+    use super::*;
+    use crate::schema::todos::dsl::*;
+
+    #[test]
+    fn generated_sql_should_match_snapshots() {
+        let query = todos.select(title).filter(id.eq(1));
+        assert_sql_eq(
+            &query,
+            r#"SELECT "todos"."title"
+               FROM "todos"
+               WHERE ("todos"."id" = $1) -- binds: [1]"#,
+        );
+
+        let dir = env::temp_dir().join(format!("testdb-snapshots-{}", std::process::id()));
+        assert_sql_snapshot_in(&dir, "todo_title", &query);
+        assert_sql_snapshot_in(&dir, "todo_title", &query);
+        let changed = std::panic::catch_unwind(|| {
+            assert_sql_snapshot_in(&dir, "todo_title", &todos.select(title))
+        });
+        assert!(changed.is_err());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,247 @@
+//! The administrative statements run against the server, shared by the real
+//! setup/teardown and the dry-run plan so both always agree.
+
+/// Options of `CREATE DATABASE` set with the builder, e.g.
+/// [`encoding`](crate::TestDbBuilder::encoding).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct DatabaseOptions {
+    pub encoding: Option<String>,
+    pub lc_collate: Option<String>,
+    pub lc_ctype: Option<String>,
+    pub owner: Option<String>,
+    pub template0: bool,
+}
+
+impl DatabaseOptions {
+    /// Whether the database has to be cloned from `template0`, the only
+    /// template that can be copied with a different encoding or locale.
+    pub fn needs_template0(&self) -> bool {
+        self.template0
+            || self.encoding.is_some()
+            || self.lc_collate.is_some()
+            || self.lc_ctype.is_some()
+    }
+}
+
+pub(crate) fn create_database(
+    dbname: &str,
+    template: Option<&str>,
+    options: Option<&DatabaseOptions>,
+) -> String {
+    let options = options.cloned().unwrap_or_default();
+    let template = template.or(options.needs_template0().then_some("template0"));
+    let mut sql = format!(r#"CREATE DATABASE "{}""#, dbname);
+    if let Some(template) = template {
+        sql.push_str(&format!(r#" TEMPLATE "{}""#, template));
+    }
+    if let Some(owner) = &options.owner {
+        sql.push_str(&format!(" OWNER {}", quote_ident(owner)));
+    }
+    if let Some(encoding) = &options.encoding {
+        sql.push_str(&format!(" ENCODING {}", quote_literal(encoding)));
+    }
+    if let Some(lc_collate) = &options.lc_collate {
+        sql.push_str(&format!(" LC_COLLATE {}", quote_literal(lc_collate)));
+    }
+    if let Some(lc_ctype) = &options.lc_ctype {
+        sql.push_str(&format!(" LC_CTYPE {}", quote_literal(lc_ctype)));
+    }
+    sql
+}
+
+pub(crate) fn terminate_connections(dbname: &str) -> String {
+    format!(
+        r#"SELECT pg_terminate_backend(pid) FROM pg_stat_activity WHERE pid <> pg_backend_pid() AND datname = '{}'"#,
+        dbname
+    )
+}
+
+pub(crate) fn drop_database(dbname: &str) -> String {
+    format!(r#"DROP DATABASE "{}""#, dbname)
+}
+
+/// `DROP DATABASE` terminating the remaining connections itself, Postgres 13+.
+pub(crate) fn drop_database_force(dbname: &str) -> String {
+    format!("{} WITH (FORCE)", drop_database(dbname))
+}
+
+/// Drop the role created for the test database `name` by
+/// [`AppRole`](crate::AppRole), if there is one.
+pub(crate) fn drop_role(name: &str) -> String {
+    format!("DROP ROLE IF EXISTS {}", quote_ident(name))
+}
+
+pub(crate) fn rename_database(dbname: &str, new_name: &str) -> String {
+    format!(
+        "ALTER DATABASE {} RENAME TO {}",
+        quote_ident(dbname),
+        quote_ident(new_name)
+    )
+}
+
+pub(crate) fn comment_on_database(dbname: &str, comment: &str) -> String {
+    format!(
+        "COMMENT ON DATABASE {} IS {}",
+        quote_ident(dbname),
+        quote_literal(comment)
+    )
+}
+
+pub(crate) fn connection_limit(dbname: &str, limit: u32) -> String {
+    format!(
+        "ALTER DATABASE {} CONNECTION LIMIT {}",
+        quote_ident(dbname),
+        limit
+    )
+}
+
+pub(crate) fn alter_database_set(dbname: &str, name: &str, value: &str) -> String {
+    format!(
+        "ALTER DATABASE {} SET {} = {}",
+        quote_ident(dbname),
+        name,
+        quote_literal(value)
+    )
+}
+
+pub(crate) fn create_schema(schema: &str) -> String {
+    format!("CREATE SCHEMA {}", quote_ident(schema))
+}
+
+pub(crate) fn drop_schema(schema: &str) -> String {
+    format!("DROP SCHEMA IF EXISTS {} CASCADE", quote_ident(schema))
+}
+
+/// Resolve unqualified names in `schema` first, falling back to `public` for
+/// the extensions installed there.
+pub(crate) fn set_search_path(schema: &str) -> String {
+    format!("SET search_path TO {}, public", quote_ident(schema))
+}
+
+pub(crate) fn set(name: &str, value: &str) -> String {
+    format!("SET {} TO {}", quote_ident(name), quote_literal(value))
+}
+
+pub(crate) fn create_extension(name: &str) -> String {
+    format!("CREATE EXTENSION IF NOT EXISTS {}", quote_ident(name))
+}
+
+pub(crate) fn create_enum(name: &str, labels: &[&str]) -> String {
+    let labels = labels
+        .iter()
+        .map(|label| quote_literal(label))
+        .collect::<Vec<_>>();
+    format!(
+        "CREATE TYPE {} AS ENUM ({})",
+        quote_ident(name),
+        labels.join(", ")
+    )
+}
+
+pub(crate) fn create_domain(name: &str, base_type: &str, check: Option<&str>) -> String {
+    match check {
+        Some(check) => format!(
+            "CREATE DOMAIN {} AS {} CHECK ({})",
+            quote_ident(name),
+            base_type,
+            check
+        ),
+        None => format!("CREATE DOMAIN {} AS {}", quote_ident(name), base_type),
+    }
+}
+
+pub(crate) fn create_composite(name: &str, fields: &[(&str, &str)]) -> String {
+    let fields = fields
+        .iter()
+        .map(|(field, sql_type)| format!("{} {}", quote_ident(field), sql_type))
+        .collect::<Vec<_>>();
+    format!(
+        "CREATE TYPE {} AS ({})",
+        quote_ident(name),
+        fields.join(", ")
+    )
+}
+
+/// Quote a string literal, `it's` -> `'it''s'`.
+pub(crate) fn quote_literal(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+/// Quote an identifier such as a table or column name, `users` -> `"users"`.
+/// Schema-qualified names (`app.users`) are quoted part by part.
+pub(crate) fn quote_ident(name: &str) -> String {
+    name.split('.')
+        .map(|part| format!(r#""{}""#, part.replace('"', r#""""#)))
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn identifiers_should_be_quoted() {
+        assert_eq!(quote_ident("todos"), r#""todos""#);
+        assert_eq!(quote_ident("app.todos"), r#""app"."todos""#);
+        assert_eq!(quote_ident(r#"we"ird"#), r#""we""ird""#);
+    }
+
+    #[test]
+    fn collated_databases_should_come_from_template0() {
+        let collated = DatabaseOptions {
+            lc_collate: Some("de_DE.utf8".into()),
+            lc_ctype: Some("de_DE.utf8".into()),
+            ..Default::default()
+        };
+        assert_eq!(
+            create_database("test_1", None, Some(&collated)),
+            r#"CREATE DATABASE "test_1" TEMPLATE "template0" LC_COLLATE 'de_DE.utf8' LC_CTYPE 'de_DE.utf8'"#
+        );
+        let owned = DatabaseOptions {
+            owner: Some("app".into()),
+            encoding: Some("LATIN1".into()),
+            ..Default::default()
+        };
+        assert_eq!(
+            create_database("test_1", None, Some(&owned)),
+            r#"CREATE DATABASE "test_1" TEMPLATE "template0" OWNER "app" ENCODING 'LATIN1'"#
+        );
+        let owned = DatabaseOptions {
+            owner: Some("app".into()),
+            ..Default::default()
+        };
+        assert_eq!(
+            create_database("test_1", Some("tpl_1"), Some(&owned)),
+            r#"CREATE DATABASE "test_1" TEMPLATE "tpl_1" OWNER "app""#
+        );
+        assert_eq!(
+            create_database("test_1", Some("tpl_1"), None),
+            r#"CREATE DATABASE "test_1" TEMPLATE "tpl_1""#
+        );
+    }
+
+    #[test]
+    fn forced_drop_should_extend_the_plain_drop() {
+        assert_eq!(
+            drop_database_force("test_1"),
+            r#"DROP DATABASE "test_1" WITH (FORCE)"#
+        );
+    }
+
+    #[test]
+    fn type_definitions_should_be_quoted() {
+        assert_eq!(
+            create_enum("status", &["open", "won't fix"]),
+            r#"CREATE TYPE "status" AS ENUM ('open', 'won''t fix')"#
+        );
+        assert_eq!(
+            create_domain("positive", "int4", Some("VALUE > 0")),
+            r#"CREATE DOMAIN "positive" AS int4 CHECK (VALUE > 0)"#
+        );
+        assert_eq!(
+            create_composite("address", &[("street", "text"), ("zip", "int4")]),
+            r#"CREATE TYPE "address" AS ("street" text, "zip" int4)"#
+        );
+    }
+}
@@ -0,0 +1,230 @@
+//! SQLite databases for tests that don't need a running Postgres.
+//!
+//! The database lives in a temporary file removed on drop, or in memory,
+//! shared by all connections of the process through SQLite's shared cache and
+//! kept alive by a connection held by the [`SqliteTestDb`].
+
+use std::{fs, path::PathBuf, sync::Mutex};
+
+use diesel::{
+    connection::SimpleConnection,
+    migration::MigrationSource,
+    r2d2::{self, ConnectionManager, CustomizeConnection},
+    sqlite::Sqlite,
+    Connection, ConnectionError, SqliteConnection,
+};
+use diesel_migrations::MigrationHarness;
+use log::{error, info};
+
+use crate::{
+    diagnostics,
+    naming::{self, DbNaming},
+    report, setup_phase, TestDbError,
+};
+
+/// A connection pool to a [`SqliteTestDb`].
+pub type SqlitePool = r2d2::Pool<ConnectionManager<SqliteConnection>>;
+
+/// Run on every connection: wait for locks held by the other connections
+/// instead of failing with `database is locked`.
+const SESSION_SQL: &str = "PRAGMA busy_timeout = 5000";
+
+/// A SQLite database created for a test and deleted with it.
+///
+/// ```
+/// use diesel_database_tester::SqliteTestDb;
+/// use diesel_migrations::{embed_migrations, EmbeddedMigrations};
+///
+/// const MIGRATIONS: EmbeddedMigrations = embed_migrations!("./migrations_sqlite");
+///
+/// let tdb = SqliteTestDb::new(MIGRATIONS);
+/// let pool = tdb.pool();
+/// ```
+pub struct SqliteTestDb {
+    pub dbname: String,
+    /// The database file, `None` for an in-memory database.
+    pub path: Option<PathBuf>,
+    /// Keeps an in-memory database alive while no other connection is open.
+    keeper: Option<Mutex<SqliteConnection>>,
+}
+
+impl SqliteTestDb {
+    /// Create a database in a file in the temp directory and run
+    /// `migrations` in it.
+    ///
+    /// # Panics
+    ///
+    /// If the database can't be created or migrated, see [`try_new`](Self::try_new).
+    pub fn new(migrations: impl MigrationSource<Sqlite>) -> Self {
+        Self::try_new(migrations).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Like [`new`](Self::new), returning an error instead of panicking.
+    pub fn try_new(migrations: impl MigrationSource<Sqlite>) -> Result<Self, TestDbError> {
+        let dbname = database_name();
+        let path = std::env::temp_dir().join(format!("{}.sqlite3", dbname));
+        Self::create(dbname, Some(path), migrations)
+    }
+
+    /// Create a database in memory and run `migrations` in it. Faster than a
+    /// file, but every connection shares the same lock.
+    ///
+    /// # Panics
+    ///
+    /// If the database can't be created or migrated, see
+    /// [`try_in_memory`](Self::try_in_memory).
+    pub fn in_memory(migrations: impl MigrationSource<Sqlite>) -> Self {
+        Self::try_in_memory(migrations).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Like [`in_memory`](Self::in_memory), returning an error instead of
+    /// panicking.
+    pub fn try_in_memory(migrations: impl MigrationSource<Sqlite>) -> Result<Self, TestDbError> {
+        Self::create(database_name(), None, migrations)
+    }
+
+    fn create(
+        dbname: String,
+        path: Option<PathBuf>,
+        migrations: impl MigrationSource<Sqlite>,
+    ) -> Result<Self, TestDbError> {
+        diagnostics::log(format_args!("creating sqlite database {}", dbname));
+        // from here on the database may exist, so dropping `tdb` cleans it up on error
+        let mut tdb = Self {
+            dbname,
+            path,
+            keeper: None,
+        };
+        let mut conn = connect_to(&tdb.url())?;
+        setup_phase(&tdb.dbname, "run migrations", || {
+            conn.run_pending_migrations(migrations).map(|_| ())
+        })?;
+        if tdb.path.is_none() {
+            tdb.keeper = Some(Mutex::new(conn));
+        }
+        Ok(tdb)
+    }
+
+    /// The url of the test database, a path or a `file:` uri.
+    pub fn url(&self) -> String {
+        match &self.path {
+            Some(path) => path.display().to_string(),
+            None => format!("file:{}?mode=memory&cache=shared", self.dbname),
+        }
+    }
+
+    /// Open a new connection to the test database.
+    pub fn connect(&self) -> SqliteConnection {
+        connect_to(&self.url()).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// A connection pool to the test database.
+    pub fn pool(&self) -> SqlitePool {
+        self.try_pool().unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Like [`pool`](Self::pool), returning an error instead of panicking.
+    pub fn try_pool(&self) -> Result<SqlitePool, TestDbError> {
+        r2d2::Pool::builder()
+            .connection_customizer(Box::new(SessionSql))
+            .build(ConnectionManager::new(self.url()))
+            .map_err(TestDbError::Pool)
+    }
+}
+
+fn database_name() -> String {
+    naming::database_name(
+        DbNaming::Short,
+        naming::DEFAULT_PREFIX,
+        report::current_test().as_deref(),
+    )
+}
+
+fn connect_to(url: &str) -> Result<SqliteConnection, TestDbError> {
+    let connect = || {
+        let mut conn = SqliteConnection::establish(url)?;
+        conn.batch_execute(SESSION_SQL)
+            .map_err(ConnectionError::CouldntSetupConfiguration)?;
+        Ok(conn)
+    };
+    connect().map_err(|source| TestDbError::Connect {
+        url: url.to_string(),
+        source,
+    })
+}
+
+#[derive(Debug)]
+struct SessionSql;
+
+impl CustomizeConnection<SqliteConnection, r2d2::Error> for SessionSql {
+    fn on_acquire(&self, conn: &mut SqliteConnection) -> Result<(), r2d2::Error> {
+        conn.batch_execute(SESSION_SQL)
+            .map_err(r2d2::Error::QueryError)
+    }
+}
+
+impl Drop for SqliteTestDb {
+    fn drop(&mut self) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        info!("Deleting sqlite database {}", path.display());
+        for suffix in ["", "-journal", "-wal", "-shm"] {
+            let mut file = path.clone().into_os_string();
+            file.push(suffix);
+            match fs::remove_file(&file) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                    error!("Error while deleting {}: {}", path.display(), e)
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use diesel::{dsl::sql, sql_types::BigInt, RunQueryDsl};
+    use diesel_migrations::{embed_migrations, EmbeddedMigrations};
+
+    const MIGRATIONS: EmbeddedMigrations = embed_migrations!("./migrations_sqlite");
+
+    fn count_todos(conn: &mut SqliteConnection) -> i64 {
+        diesel::select(sql::<BigInt>("(SELECT COUNT(*) FROM todos)"))
+            .get_result(conn)
+            .unwrap()
+    }
+
+    #[test]
+    fn sqlite_test_db_should_delete_its_file_on_drop() {
+        let tdb = SqliteTestDb::new(MIGRATIONS);
+        let path = tdb.path.clone().unwrap();
+        assert!(path.exists());
+        assert!(tdb
+            .dbname
+            .ends_with("sqlite_test_db_should_delete_its_file_on_drop"));
+
+        tdb.connect()
+            .batch_execute("INSERT INTO todos (title) VALUES ('sqlite')")
+            .unwrap();
+        assert_eq!(count_todos(&mut tdb.pool().get().unwrap()), 1);
+        drop(tdb);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn in_memory_sqlite_test_db_should_be_shared_by_its_connections() {
+        let tdb = SqliteTestDb::in_memory(MIGRATIONS);
+        assert_eq!(tdb.path, None);
+        tdb.connect()
+            .batch_execute("INSERT INTO todos (title) VALUES ('sqlite')")
+            .unwrap();
+        let pool = tdb.pool();
+        assert_eq!(count_todos(&mut pool.get().unwrap()), 1);
+
+        let other = SqliteTestDb::in_memory(MIGRATIONS);
+        assert_eq!(count_todos(&mut other.connect()), 0);
+    }
+}
@@ -0,0 +1,52 @@
+//! An sqlx pool to the test database, behind the `sqlx` feature, for code
+//! using sqlx against a schema migrated with diesel.
+
+use sqlx::{postgres::PgPoolOptions, Executor, PgPool};
+
+use crate::{TestDb, TestDbError};
+
+impl TestDb {
+    /// An sqlx [`PgPool`] to the test database, with the session settings of
+    /// [`pool`](Self::pool) and sized by
+    /// [`TestDbBuilder::pool_size`](crate::TestDbBuilder::pool_size).
+    ///
+    /// ```no_run
+    /// # async fn example() {
+    /// use diesel_database_tester::TestDb;
+    ///
+    /// let tdb = TestDb::builder().build();
+    /// let pool = tdb.sqlx_pool().await;
+    /// let (count,): (i64,) = sqlx::query_as("SELECT count(*) FROM todos")
+    ///     .fetch_one(&pool)
+    ///     .await
+    ///     .unwrap();
+    /// # }
+    /// ```
+    pub async fn sqlx_pool(&self) -> PgPool {
+        self.try_sqlx_pool()
+            .await
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Like [`sqlx_pool`](Self::sqlx_pool), returning the error instead of
+    /// panicking.
+    pub async fn try_sqlx_pool(&self) -> Result<PgPool, TestDbError> {
+        let session_sql = self.config.session_sql.clone();
+        let mut options = PgPoolOptions::new().after_connect(move |conn, _| {
+            let session_sql = session_sql.clone();
+            Box::pin(async move {
+                for sql in &session_sql {
+                    conn.execute(sql.as_str()).await?;
+                }
+                Ok(())
+            })
+        });
+        if let Some(size) = self.pool_size {
+            options = options.max_connections(size);
+        }
+        options
+            .connect(&self.url())
+            .await
+            .map_err(TestDbError::SqlxPool)
+    }
+}
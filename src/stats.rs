@@ -0,0 +1,81 @@
+//! Assertions on table access counters from `pg_stat_user_tables`.
+
+use diesel::{
+    sql_query,
+    sql_types::{BigInt, Text},
+    PgConnection, QueryResult, QueryableByName, RunQueryDsl,
+};
+
+use crate::{diagnostics, TestDb};
+
+/// How often a table was read through sequential and index scans.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, QueryableByName)]
+pub struct TableScans {
+    #[diesel(sql_type = BigInt)]
+    pub seq_scan: i64,
+    #[diesel(sql_type = BigInt)]
+    pub idx_scan: i64,
+}
+
+#[derive(QueryableByName)]
+struct ServerVersion {
+    #[diesel(sql_type = BigInt)]
+    version: i64,
+}
+
+impl TestDb {
+    /// Reset the statistics counters of the test database.
+    pub fn stat_reset(&self) -> QueryResult<()> {
+        diagnostics::execute(&mut self.connect(), "SELECT pg_stat_reset()")?;
+        Ok(())
+    }
+
+    /// Current scan counters of `table`, as seen by `conn`.
+    pub fn table_scans(&self, conn: &mut PgConnection, table: &str) -> QueryResult<TableScans> {
+        flush_stats(conn)?;
+        sql_query(
+            "SELECT COALESCE(seq_scan, 0)::bigint AS seq_scan, COALESCE(idx_scan, 0)::bigint AS idx_scan \
+             FROM pg_stat_user_tables WHERE relid = $1::regclass",
+        )
+        .bind::<Text, _>(table)
+        .get_result(conn)
+    }
+
+    /// Run `f` on a dedicated connection and panic if it made the server
+    /// sequentially scan `table`, so accidental full scans introduced by
+    /// query changes fail tests.
+    pub fn assert_no_seq_scans<T>(&self, table: &str, f: impl FnOnce(&mut PgConnection) -> T) -> T {
+        let mut conn = self.connect();
+        let before = self
+            .table_scans(&mut conn, table)
+            .expect("Failed to read table statistics");
+        let result = f(&mut conn);
+        let after = self
+            .table_scans(&mut conn, table)
+            .expect("Failed to read table statistics");
+        let seq_scans = after.seq_scan - before.seq_scan;
+        assert!(
+            seq_scans == 0,
+            "expected no sequential scans on {}, found {}",
+            table,
+            seq_scans
+        );
+        result
+    }
+}
+
+/// Make the statistics of `conn` visible before reading counters.
+fn flush_stats(conn: &mut PgConnection) -> QueryResult<()> {
+    let version = sql_query("SELECT current_setting('server_version_num')::bigint AS version")
+        .get_result::<ServerVersion>(conn)?
+        .version;
+    if version >= 150000 {
+        // the backend reports its pending stats before answering this statement
+        sql_query("SELECT pg_stat_force_next_flush()").execute(conn)?;
+    } else {
+        // the stats collector receives updates at most every 500ms
+        std::thread::sleep(std::time::Duration::from_millis(600));
+    }
+    sql_query("SELECT pg_stat_clear_snapshot()").execute(conn)?;
+    Ok(())
+}
@@ -0,0 +1,61 @@
+//! Row-by-row processing of large results through a server-side cursor.
+
+use diesel::{
+    connection::SimpleConnection, pg::Pg, sql_query, Connection, QueryResult, QueryableByName,
+    RunQueryDsl,
+};
+
+use crate::{diagnostics, TestDb};
+
+/// Rows fetched from the cursor per round trip.
+const FETCH_SIZE: usize = 1_000;
+
+impl TestDb {
+    /// Run `sql` and call `f` for every row, holding at most a few thousand
+    /// rows in memory at a time, so invariants can be checked over results
+    /// too large to load at once. Returns the number of rows seen.
+    ///
+    /// ```no_run
+    /// # use diesel_database_tester::TestDb;
+    /// # let tdb = TestDb::builder().build();
+    /// use diesel::{sql_types::Integer, QueryableByName};
+    ///
+    /// #[derive(QueryableByName)]
+    /// struct Id {
+    ///     #[diesel(sql_type = Integer)]
+    ///     id: i32,
+    /// }
+    ///
+    /// let mut last = 0;
+    /// tdb.stream_query("SELECT id FROM todos ORDER BY id", |row: Id| {
+    ///     assert!(row.id > last);
+    ///     last = row.id;
+    /// })
+    /// .unwrap();
+    /// ```
+    pub fn stream_query<T>(&self, sql: &str, mut f: impl FnMut(T)) -> QueryResult<usize>
+    where
+        T: QueryableByName<Pg> + 'static,
+    {
+        let mut conn = self.connect();
+        conn.transaction(|conn| {
+            diagnostics::execute(
+                conn,
+                &format!("DECLARE testdb_stream NO SCROLL CURSOR FOR {}", sql),
+            )?;
+            let mut seen = 0;
+            loop {
+                let rows = sql_query(format!("FETCH {} FROM testdb_stream", FETCH_SIZE))
+                    .load::<T>(conn)?;
+                let fetched = rows.len();
+                seen += fetched;
+                rows.into_iter().for_each(&mut f);
+                if fetched < FETCH_SIZE {
+                    break;
+                }
+            }
+            conn.batch_execute("CLOSE testdb_stream")?;
+            Ok(seen)
+        })
+    }
+}
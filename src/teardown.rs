@@ -0,0 +1,86 @@
+//! Keeping teardown from hanging or escalating: every drop is bounded by a
+//! timeout, and a database that couldn't be dropped is logged and remembered
+//! instead of panicking, which would abort a test that is already failing.
+
+use std::{
+    sync::{Mutex, PoisonError},
+    time::Duration,
+};
+
+/// How long dropping a database may take unless the builder says otherwise,
+/// see [`TestDbBuilder::drop_timeout`](crate::TestDbBuilder::drop_timeout).
+pub(crate) const DROP_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A test database that was left behind because dropping it failed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LeakedDatabase {
+    pub database: String,
+    /// The test that owned the database, when known.
+    pub test: Option<String>,
+    /// Why it couldn't be dropped.
+    pub error: String,
+}
+
+static LEAKED: Mutex<Vec<LeakedDatabase>> = Mutex::new(Vec::new());
+
+pub(crate) fn record_leak(database: &str, test: Option<&str>, error: &str) {
+    LEAKED
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .push(LeakedDatabase {
+            database: database.to_string(),
+            test: test.map(str::to_string),
+            error: error.to_string(),
+        });
+}
+
+/// Every test database that couldn't be dropped so far in this run, e.g. to
+/// fail a CI job or clean them up at its end.
+pub fn leaked_databases() -> Vec<LeakedDatabase> {
+    LEAKED
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone()
+}
+
+/// `url` giving up on connecting after `timeout`, unless it already sets a
+/// `connect_timeout` of its own.
+pub(crate) fn bounded_url(url: &str, timeout: Duration) -> String {
+    if url.contains("connect_timeout=") {
+        return url.to_string();
+    }
+    let separator = if url.contains('?') { '&' } else { '?' };
+    let secs = timeout.as_secs() + u64::from(timeout.subsec_nanos() > 0);
+    format!("{}{}connect_timeout={}", url, separator, secs.max(1))
+}
+
+/// The statements bounding how long the drop connection waits on the server.
+pub(crate) fn session_limits(timeout: Duration) -> [String; 2] {
+    let ms = timeout.as_millis().max(1);
+    [
+        format!("SET statement_timeout = {}", ms),
+        format!("SET lock_timeout = {}", ms),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn urls_should_get_a_connect_timeout() {
+        let timeout = Duration::from_millis(2500);
+        assert_eq!(
+            bounded_url("postgres://u:p@localhost:5432", timeout),
+            "postgres://u:p@localhost:5432?connect_timeout=3"
+        );
+        assert_eq!(
+            bounded_url("postgres://u:p@localhost:5432?sslmode=disable", timeout),
+            "postgres://u:p@localhost:5432?sslmode=disable&connect_timeout=3"
+        );
+        assert_eq!(
+            bounded_url("postgres://localhost?connect_timeout=10", timeout),
+            "postgres://localhost?connect_timeout=10"
+        );
+    }
+}
@@ -0,0 +1,52 @@
+//! Fast mode keeping the whole schema in `pg_temp` on a single connection.
+//!
+//! No database is created: the migrations run with `search_path` set to
+//! `pg_temp`, so every table lands in the session's temporary schema and is
+//! gone as soon as the connection closes. Good for unit-style tests needing a
+//! couple of tables; anything relying on a second connection, extensions,
+//! or unqualified calls to functions defined by the migrations won't work.
+
+use diesel::{PgConnection, RunQueryDsl};
+
+use crate::{
+    connection::{redact_url, ConnectionConfig},
+    diagnostics, establish_connection,
+    migrations::MigrationSet,
+    run_migrations,
+};
+
+/// A schema living in the temporary schema of its only connection.
+pub struct TempSchemaDb {
+    conn: PgConnection,
+}
+
+impl TempSchemaDb {
+    pub(crate) fn create(config: &ConnectionConfig, migrations: &MigrationSet) -> Self {
+        let server_url = config
+            .server_url()
+            .unwrap_or_else(|e| panic!("Failed to resolve the server url: {}", e));
+        diagnostics::log(format_args!(
+            "creating temp schema on {}",
+            redact_url(&server_url)
+        ));
+        let mut conn =
+            diagnostics::phase("connect to server", || establish_connection(&server_url));
+        for sql in &config.session_sql {
+            diesel::sql_query(sql)
+                .execute(&mut conn)
+                .unwrap_or_else(|e| panic!("Failed to run {}: {}", sql, e));
+        }
+        diagnostics::execute(&mut conn, "SET search_path TO pg_temp")
+            .expect("Failed to switch to the temp schema");
+        diagnostics::phase("run migrations", || {
+            run_migrations(&mut conn, migrations, &|_| {})
+        })
+        .unwrap_or_else(|e| panic!("Failed to run migrations in pg_temp: {}", e));
+        Self { conn }
+    }
+
+    /// The connection owning the temp schema.
+    pub fn conn(&mut self) -> &mut PgConnection {
+        &mut self.conn
+    }
+}
@@ -0,0 +1,213 @@
+//! Long-lived template databases shared by every process using the server.
+//!
+//! The migrated schema lives in `tpl_<project>_<hash>`, the project hash
+//! covering the crate under test (`$CARGO_PKG_NAME`), the other one the
+//! names and SQL of all migrations and the statements run before them. The
+//! first process to need it builds it while holding an advisory lock, so
+//! concurrent test binaries and CI jobs wait for it instead of racing;
+//! everyone then clones it with `CREATE DATABASE ... TEMPLATE`. Every process
+//! records when it first used a template in the template's comment, and
+//! whenever a new one is built the project's templates unused for a day are
+//! dropped; templates of other projects are left alone. Each process
+//! remembers the templates it has seen, so only its first database pays for
+//! the lock and the lookup.
+
+use std::{
+    collections::HashSet,
+    env,
+    error::Error,
+    sync::{Mutex, OnceLock},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use diesel::{
+    sql_types::{Nullable, Text},
+    Connection, PgConnection, QueryableByName, RunQueryDsl,
+};
+use log::{info, warn};
+
+use crate::{
+    connection::with_database, diagnostics, migrations::MigrationSet, run_migrations, schema_cache,
+    sql, MigrationProgress,
+};
+
+pub(crate) const PREFIX: &str = "tpl_";
+/// Advisory lock serializing template builds, `testdb` in ascii.
+const LOCK_KEY: i64 = 0x7465_7374_6462;
+/// How long a template of the project has to go unused to be collected.
+const UNUSED_FOR: Duration = Duration::from_secs(24 * 60 * 60);
+/// The comment recording when a template was last used, followed by the
+/// unix time.
+const LAST_USED: &str = "testdb template last used at ";
+
+/// Templates known to exist, as `(server url, template)`.
+static KNOWN: OnceLock<Mutex<HashSet<(String, String)>>> = OnceLock::new();
+
+fn known() -> &'static Mutex<HashSet<(String, String)>> {
+    KNOWN.get_or_init(Default::default)
+}
+
+/// Forget that `template` exists, e.g. after cloning it failed because
+/// another process dropped it, so the next [`ensure`] checks again.
+pub(crate) fn forget(server_url: &str, template: &str) {
+    known()
+        .lock()
+        .unwrap()
+        .remove(&(server_url.to_string(), template.to_string()));
+}
+
+#[derive(QueryableByName)]
+struct Datname {
+    #[diesel(sql_type = Text)]
+    datname: String,
+}
+
+#[derive(QueryableByName)]
+struct Template {
+    #[diesel(sql_type = Text)]
+    datname: String,
+    #[diesel(sql_type = Nullable<Text>)]
+    comment: Option<String>,
+}
+
+/// The start of the names of this project's templates.
+pub(crate) fn project_prefix() -> String {
+    let project = env::var("CARGO_PKG_NAME").unwrap_or_default();
+    format!(
+        "{}{:08x}_",
+        PREFIX,
+        schema_cache::stable_hash([project]) as u32
+    )
+}
+
+/// Name of the template for the current migrations.
+pub(crate) fn name(migrations: &MigrationSet, setup_sql: &[String]) -> String {
+    format!(
+        "{}{:016x}",
+        project_prefix(),
+        schema_cache::setup_hash(migrations, setup_sql)
+    )
+}
+
+/// Make sure the template for the current migrations exists, building it if
+/// needed. Returns its name.
+pub(crate) fn ensure(
+    conn: &mut PgConnection,
+    server_url: &str,
+    migrations: &MigrationSet,
+    setup_sql: &[String],
+    on_migration: &dyn Fn(&MigrationProgress),
+) -> Result<String, Box<dyn Error + Send + Sync + 'static>> {
+    let template = name(migrations, setup_sql);
+    let key = (server_url.to_string(), template.clone());
+    if known().lock().unwrap().contains(&key) {
+        return Ok(template);
+    }
+    diagnostics::execute(conn, &format!("SELECT pg_advisory_lock({})", LOCK_KEY))?;
+    let result = build_if_missing(
+        conn,
+        server_url,
+        &template,
+        migrations,
+        setup_sql,
+        on_migration,
+    );
+    if result.is_ok() {
+        mark_used(conn, &template, SystemTime::now());
+    }
+    diagnostics::execute(conn, &format!("SELECT pg_advisory_unlock({})", LOCK_KEY))?;
+    result?;
+    known().lock().unwrap().insert(key);
+    Ok(template)
+}
+
+fn build_if_missing(
+    conn: &mut PgConnection,
+    server_url: &str,
+    template: &str,
+    migrations: &MigrationSet,
+    setup_sql: &[String],
+    on_migration: &dyn Fn(&MigrationProgress),
+) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+    if templates(conn)?.iter().any(|dbname| dbname == template) {
+        return Ok(());
+    }
+    // migrate under another name so a crashed build never looks finished
+    let building = format!("{}_building", template);
+    diagnostics::execute(conn, &format!(r#"DROP DATABASE IF EXISTS "{}""#, building))?;
+    diagnostics::execute(conn, &sql::create_database(&building, None, None))?;
+    {
+        let mut tpl_conn = PgConnection::establish(&with_database(server_url, &building))?;
+        for sql in setup_sql {
+            diagnostics::execute(&mut tpl_conn, sql)?;
+        }
+        run_migrations(&mut tpl_conn, migrations, on_migration)?;
+    }
+    diagnostics::execute(
+        conn,
+        &format!(r#"ALTER DATABASE "{}" RENAME TO "{}""#, building, template),
+    )?;
+    info!("Built template database {}", template);
+    collect_garbage(conn, template);
+    Ok(())
+}
+
+/// Record in its comment that `template` was used at `now`, keeping it from
+/// being collected for another [`UNUSED_FOR`].
+pub(crate) fn mark_used(conn: &mut PgConnection, template: &str, now: SystemTime) {
+    let secs = now.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+    let comment = format!(
+        "COMMENT ON DATABASE {} IS {}",
+        sql::quote_ident(template),
+        sql::quote_literal(&format!("{}{}", LAST_USED, secs))
+    );
+    if let Err(e) = diagnostics::execute(conn, &comment) {
+        warn!(
+            "Failed to mark template database {} as used: {}",
+            template, e
+        );
+    }
+}
+
+/// Drop this project's templates other than `current` that went unused for
+/// [`UNUSED_FOR`], or never finished building. Templates still being cloned
+/// by jobs running older migrations fail to drop and are kept.
+pub(crate) fn collect_garbage(conn: &mut PgConnection, current: &str) {
+    let owned = diesel::sql_query(
+        "SELECT datname::text, shobj_description(oid, 'pg_database') AS comment \
+         FROM pg_database WHERE starts_with(datname, $1)",
+    )
+    .bind::<Text, _>(project_prefix())
+    .load::<Template>(conn);
+    let owned = match owned {
+        Ok(owned) => owned,
+        Err(e) => {
+            warn!("Failed to list template databases: {}", e);
+            return;
+        }
+    };
+    let now = SystemTime::now();
+    for Template { datname, comment } in owned {
+        let last_used = comment
+            .as_deref()
+            .and_then(|comment| comment.strip_prefix(LAST_USED)?.parse().ok())
+            .map(|secs| UNIX_EPOCH + Duration::from_secs(secs));
+        let unused =
+            last_used.is_none_or(|used| now.duration_since(used).unwrap_or_default() >= UNUSED_FOR);
+        if datname == current || !unused {
+            continue;
+        }
+        match diagnostics::execute(conn, &format!(r#"DROP DATABASE IF EXISTS "{}""#, datname)) {
+            Ok(_) => info!("Dropped stale template database {}", datname),
+            Err(e) => warn!("Failed to drop stale template database {}: {}", datname, e),
+        }
+    }
+}
+
+/// Every template database on the server, including unfinished builds.
+fn templates(conn: &mut PgConnection) -> diesel::QueryResult<Vec<String>> {
+    diesel::sql_query("SELECT datname::text FROM pg_database WHERE starts_with(datname, $1)")
+        .bind::<Text, _>(PREFIX)
+        .load::<Datname>(conn)
+        .map(|rows| rows.into_iter().map(|row| row.datname).collect())
+}
@@ -0,0 +1,143 @@
+//! Bounding how many test databases are set up or dropped at once, so a test
+//! binary running many threads doesn't open more connections than a small
+//! server allows. Tests wait for their turn instead of failing with
+//! `too many clients already`.
+//!
+//! The limit is process-wide: 4 by default, `$TESTDB_MAX_CONCURRENT_SETUPS`
+//! or [`set_max_concurrent_setups`], 0 for no limit.
+
+use std::{
+    cell::Cell,
+    env,
+    sync::{Condvar, Mutex, OnceLock, PoisonError},
+};
+
+const DEFAULT_MAX_CONCURRENT_SETUPS: usize = 4;
+
+struct Throttle {
+    /// Limit and permits in use.
+    state: Mutex<(usize, usize)>,
+    released: Condvar,
+}
+
+static THROTTLE: OnceLock<Throttle> = OnceLock::new();
+
+thread_local! {
+    /// Whether this thread holds a permit already, e.g. dropping the database
+    /// whose setup failed, which must not wait for a second one.
+    static HOLDING: Cell<bool> = const { Cell::new(false) };
+}
+
+fn throttle() -> &'static Throttle {
+    THROTTLE.get_or_init(|| {
+        let limit = env::var("TESTDB_MAX_CONCURRENT_SETUPS")
+            .ok()
+            .and_then(|limit| limit.parse().ok())
+            .unwrap_or(DEFAULT_MAX_CONCURRENT_SETUPS);
+        Throttle {
+            state: Mutex::new((limit, 0)),
+            released: Condvar::new(),
+        }
+    })
+}
+
+/// Allow at most `limit` test databases of this process to be set up or
+/// dropped at the same time, 0 for no limit. Takes precedence over
+/// `$TESTDB_MAX_CONCURRENT_SETUPS`; setups already waiting pick it up.
+pub fn set_max_concurrent_setups(limit: usize) {
+    let throttle = throttle();
+    throttle
+        .state
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .0 = limit;
+    throttle.released.notify_all();
+}
+
+/// The turn of a setup or teardown, given back when dropped.
+pub(crate) struct Permit {
+    held: bool,
+}
+
+/// Wait until fewer than the limit of setups and teardowns are running.
+pub(crate) fn acquire() -> Permit {
+    if HOLDING.get() {
+        return Permit { held: false };
+    }
+    let throttle = throttle();
+    let mut state = throttle
+        .released
+        .wait_while(
+            throttle
+                .state
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner),
+            |(limit, running)| *limit != 0 && running >= limit,
+        )
+        .unwrap_or_else(PoisonError::into_inner);
+    state.1 += 1;
+    HOLDING.set(true);
+    Permit { held: true }
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        if !self.held {
+            return;
+        }
+        HOLDING.set(false);
+        let throttle = throttle();
+        throttle
+            .state
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .1 -= 1;
+        throttle.released.notify_one();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        thread,
+        time::Duration,
+    };
+
+    use super::*;
+
+    #[test]
+    fn permits_should_be_reentrant_and_bounded() {
+        let outer = acquire();
+        let inner = acquire();
+        assert!(outer.held && !inner.held);
+        drop(inner);
+        drop(outer);
+
+        let limit = throttle().state.lock().unwrap().0;
+        if limit == 0 {
+            return;
+        }
+        let running = Arc::new(AtomicUsize::new(0));
+        let most = Arc::new(AtomicUsize::new(0));
+        let threads: Vec<_> = (0..limit * 3)
+            .map(|_| {
+                let (running, most) = (running.clone(), most.clone());
+                thread::spawn(move || {
+                    let _permit = acquire();
+                    let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                    most.fetch_max(now, Ordering::SeqCst);
+                    thread::sleep(Duration::from_millis(20));
+                    running.fetch_sub(1, Ordering::SeqCst);
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        assert!(most.load(Ordering::SeqCst) <= limit);
+    }
+}
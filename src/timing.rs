@@ -0,0 +1,45 @@
+//! Where the setup time of a test database went, per database through
+//! [`TestDb::stats`]; the timing report written with `TESTDB_TIMING_REPORT`
+//! breaks down the setup of every database of the run the same way.
+
+use std::time::Duration;
+
+use crate::{MigrationProgress, TestDb};
+
+/// Durations of the setup of one test database.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SetupStats {
+    /// `CREATE DATABASE`, including copying the template.
+    pub create_database: Duration,
+    /// Every migration run during setup, in order; empty when the schema was
+    /// copied from the shared template or the schema cache.
+    pub migrations: Vec<MigrationProgress>,
+    /// Loading the fixtures and running the seeds.
+    pub fixtures: Duration,
+    /// Everything from connecting to the server until the database was ready.
+    pub total: Duration,
+}
+
+impl SetupStats {
+    /// The time spent in migrations altogether.
+    pub fn migrations_total(&self) -> Duration {
+        self.migrations.iter().map(|m| m.duration).sum()
+    }
+}
+
+impl TestDb {
+    /// How long the setup of this database took, broken down by phase.
+    ///
+    /// ```no_run
+    /// # use diesel_database_tester::TestDb;
+    /// let tdb = TestDb::builder().build();
+    /// let stats = tdb.stats();
+    /// for migration in &stats.migrations {
+    ///     println!("{}: {:?}", migration.name, migration.duration);
+    /// }
+    /// println!("total: {:?}", stats.total);
+    /// ```
+    pub fn stats(&self) -> &SetupStats {
+        &self.stats
+    }
+}
@@ -0,0 +1,191 @@
+//! Spans for `tracing` subscribers, emitted when the `tracing` feature is
+//! enabled, to see which part of a slow or hanging setup a test is stuck in:
+//!
+//! - `testdb.setup` around creating a database, with its `dbname`
+//! - `testdb.phase` around each setup phase, e.g. `phase="run migrations"`
+//! - `testdb.migration` around each migration applied, with its `name`
+//! - `testdb.pool` around building a pool
+//! - `testdb.drop` around dropping a database
+//!
+//! With `TestDbBuilder::trace_sql` every statement on the test database's
+//! connections is logged as a debug event with its duration.
+
+#[cfg(feature = "tracing")]
+use std::time::Instant;
+
+use diesel::PgConnection;
+
+/// An entered span, exited on drop.
+pub(crate) struct Span {
+    #[cfg(feature = "tracing")]
+    inner: ::tracing::span::EnteredSpan,
+}
+
+impl Span {
+    /// Fill in the `dbname` of a `testdb.setup` span once it is known.
+    pub fn record_dbname(&self, dbname: &str) {
+        #[cfg(feature = "tracing")]
+        self.inner.record("dbname", dbname);
+        #[cfg(not(feature = "tracing"))]
+        let _ = dbname;
+    }
+}
+
+pub(crate) fn setup() -> Span {
+    Span {
+        #[cfg(feature = "tracing")]
+        inner: ::tracing::info_span!("testdb.setup", dbname = ::tracing::field::Empty).entered(),
+    }
+}
+
+pub(crate) fn phase(name: &str) -> Span {
+    #[cfg(not(feature = "tracing"))]
+    let _ = name;
+    Span {
+        #[cfg(feature = "tracing")]
+        inner: ::tracing::info_span!("testdb.phase", phase = name).entered(),
+    }
+}
+
+pub(crate) fn migration(name: &str, index: usize, total: usize) -> Span {
+    #[cfg(not(feature = "tracing"))]
+    let _ = (name, index, total);
+    Span {
+        #[cfg(feature = "tracing")]
+        inner: ::tracing::info_span!("testdb.migration", name, index, total).entered(),
+    }
+}
+
+pub(crate) fn pool(dbname: &str) -> Span {
+    #[cfg(not(feature = "tracing"))]
+    let _ = dbname;
+    Span {
+        #[cfg(feature = "tracing")]
+        inner: ::tracing::info_span!("testdb.pool", dbname).entered(),
+    }
+}
+
+pub(crate) fn drop(dbname: &str) -> Span {
+    #[cfg(not(feature = "tracing"))]
+    let _ = dbname;
+    Span {
+        #[cfg(feature = "tracing")]
+        inner: ::tracing::info_span!("testdb.drop", dbname).entered(),
+    }
+}
+
+/// Log every statement `conn` executes from now on.
+pub(crate) fn instrument(conn: &mut PgConnection) {
+    #[cfg(feature = "tracing")]
+    diesel::Connection::set_instrumentation(conn, SqlTracing { started: None });
+    #[cfg(not(feature = "tracing"))]
+    let _ = conn;
+}
+
+#[cfg(feature = "tracing")]
+struct SqlTracing {
+    started: Option<Instant>,
+}
+
+#[cfg(feature = "tracing")]
+impl diesel::connection::Instrumentation for SqlTracing {
+    fn on_connection_event(&mut self, event: diesel::connection::InstrumentationEvent<'_>) {
+        use diesel::connection::InstrumentationEvent;
+
+        match event {
+            InstrumentationEvent::StartQuery { .. } => self.started = Some(Instant::now()),
+            InstrumentationEvent::FinishQuery { query, error, .. } => {
+                let elapsed = self.started.take().map(|start| start.elapsed());
+                match error {
+                    None => ::tracing::debug!(?elapsed, "{}", query),
+                    Some(error) => ::tracing::warn!(?elapsed, %error, "{}", query),
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(all(test, feature = "tracing"))]
+mod tests {
+    use std::{
+        fmt::{Debug, Write},
+        sync::{Arc, Mutex},
+    };
+
+    use diesel::RunQueryDsl;
+    use tracing::{
+        field::{Field, Visit},
+        span::{Attributes, Id, Record},
+        Event, Metadata, Subscriber,
+    };
+
+    use crate::TestDb;
+
+    /// Collects span names and event messages with their fields.
+    #[derive(Clone, Default)]
+    struct Recorder(Arc<Mutex<Vec<String>>>);
+
+    struct Fields(String);
+
+    impl Visit for Fields {
+        fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+            write!(self.0, " {}={:?}", field.name(), value).unwrap();
+        }
+    }
+
+    impl Subscriber for Recorder {
+        fn enabled(&self, _: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, span: &Attributes<'_>) -> Id {
+            let mut fields = Fields(span.metadata().name().to_string());
+            span.record(&mut fields);
+            let mut recorded = self.0.lock().unwrap();
+            recorded.push(fields.0);
+            Id::from_u64(recorded.len() as u64)
+        }
+
+        fn record(&self, _: &Id, _: &Record<'_>) {}
+
+        fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+        fn event(&self, event: &Event<'_>) {
+            let mut fields = Fields("event".to_string());
+            event.record(&mut fields);
+            self.0.lock().unwrap().push(fields.0);
+        }
+
+        fn enter(&self, _: &Id) {}
+
+        fn exit(&self, _: &Id) {}
+    }
+
+    #[test]
+    fn setup_and_statements_should_be_traced() {
+        let recorder = Recorder::default();
+        tracing::subscriber::with_default(recorder.clone(), || {
+            let tdb = TestDb::builder()
+                .port(15432)
+                .password("7cOPpA7dnc")
+                .shared_template(false)
+                .trace_sql()
+                .build();
+            let pool = tdb.pool();
+            diesel::sql_query("SELECT 1")
+                .execute(&mut pool.get().unwrap())
+                .unwrap();
+        });
+        let recorded = recorder.0.lock().unwrap();
+        let has = |prefix: &str| recorded.iter().any(|line| line.starts_with(prefix));
+        assert!(has("testdb.setup"), "{:?}", recorded);
+        assert!(has(r#"testdb.phase phase="run migrations""#));
+        assert!(has(
+            r#"testdb.migration name="2022-12-08-031140_todo" index=2 total=2"#
+        ));
+        assert!(has("testdb.pool dbname="));
+        assert!(has("testdb.drop dbname="));
+        assert!(has("event message=SELECT 1 -- binds: [] elapsed=Some("));
+    }
+}
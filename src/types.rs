@@ -0,0 +1,172 @@
+//! Custom enum, domain and composite types, created after the fact and
+//! introspected.
+
+use std::fmt::Debug;
+
+use diesel::{
+    deserialize::FromSqlRow,
+    dsl::sql,
+    expression::TypedExpressionType,
+    pg::Pg,
+    query_builder::QueryId,
+    select,
+    serialize::ToSql,
+    sql_query,
+    sql_types::{Array, Bool, HasSqlType, SingleValue, SqlType, Text},
+    OptionalExtension, QueryResult, QueryableByName, RunQueryDsl,
+};
+
+use crate::{diagnostics, sql, TestDb};
+
+#[derive(QueryableByName)]
+struct EnumLabels {
+    #[diesel(sql_type = Array<Text>)]
+    labels: Vec<String>,
+}
+
+#[derive(QueryableByName)]
+struct CompositeField {
+    #[diesel(sql_type = Text)]
+    name: String,
+    #[diesel(sql_type = Text)]
+    sql_type: String,
+}
+
+#[derive(QueryableByName)]
+struct DomainBase {
+    #[diesel(sql_type = Text)]
+    base_type: String,
+}
+
+impl TestDb {
+    /// Create the enum type `name` in the test database. To have it before
+    /// the migrations run, use [`TestDbBuilder::create_enum`](crate::TestDbBuilder::create_enum).
+    pub fn create_enum(&self, name: &str, labels: &[&str]) -> QueryResult<()> {
+        diagnostics::execute(&mut self.connect(), &sql::create_enum(name, labels))?;
+        Ok(())
+    }
+
+    /// Create the domain `name` over `base_type`, optionally constrained by
+    /// `check`, e.g. `VALUE > 0`.
+    pub fn create_domain(
+        &self,
+        name: &str,
+        base_type: &str,
+        check: Option<&str>,
+    ) -> QueryResult<()> {
+        diagnostics::execute(
+            &mut self.connect(),
+            &sql::create_domain(name, base_type, check),
+        )?;
+        Ok(())
+    }
+
+    /// Create the composite type `name` with `(field, sql type)` pairs.
+    pub fn create_composite(&self, name: &str, fields: &[(&str, &str)]) -> QueryResult<()> {
+        diagnostics::execute(&mut self.connect(), &sql::create_composite(name, fields))?;
+        Ok(())
+    }
+
+    /// Labels of the enum type `name` in declaration order, `None` if there
+    /// is no such enum.
+    pub fn enum_labels(&self, name: &str) -> QueryResult<Option<Vec<String>>> {
+        sql_query(
+            "SELECT ARRAY(SELECT enumlabel::text FROM pg_enum WHERE enumtypid = t.oid \
+             ORDER BY enumsortorder) AS labels \
+             FROM pg_type t WHERE t.oid = to_regtype($1) AND t.typtype = 'e'",
+        )
+        .bind::<Text, _>(name)
+        .get_result::<EnumLabels>(&mut self.connect())
+        .optional()
+        .map(|row| row.map(|row| row.labels))
+    }
+
+    /// Base type of the domain `name` as `format_type` renders it, e.g.
+    /// `integer`, `None` if there is no such domain.
+    pub fn domain_base_type(&self, name: &str) -> QueryResult<Option<String>> {
+        sql_query(
+            "SELECT format_type(t.typbasetype, t.typtypmod) AS base_type \
+             FROM pg_type t WHERE t.oid = to_regtype($1) AND t.typtype = 'd'",
+        )
+        .bind::<Text, _>(name)
+        .get_result::<DomainBase>(&mut self.connect())
+        .optional()
+        .map(|row| row.map(|row| row.base_type))
+    }
+
+    /// Fields of the composite type `name` in declaration order, with their
+    /// types as `format_type` renders them (`integer`, `text`, ...), `None` if
+    /// there is no such composite type.
+    pub fn composite_fields(&self, name: &str) -> QueryResult<Option<Vec<(String, String)>>> {
+        let mut conn = self.connect();
+        let exists = select(
+            sql::<Bool>("EXISTS (SELECT 1 FROM pg_type WHERE oid = to_regtype(")
+                .bind::<Text, _>(name)
+                .sql(") AND typtype = 'c')"),
+        )
+        .get_result::<bool>(&mut conn)?;
+        if !exists {
+            return Ok(None);
+        }
+        let fields = sql_query(
+            "SELECT a.attname::text AS name, format_type(a.atttypid, a.atttypmod) AS sql_type \
+             FROM pg_type t JOIN pg_attribute a ON a.attrelid = t.typrelid \
+             WHERE t.oid = to_regtype($1) AND a.attnum > 0 AND NOT a.attisdropped \
+             ORDER BY a.attnum",
+        )
+        .bind::<Text, _>(name)
+        .load::<CompositeField>(&mut conn)?;
+        Ok(Some(
+            fields
+                .into_iter()
+                .map(|field| (field.name, field.sql_type))
+                .collect(),
+        ))
+    }
+
+    /// Panic unless the enum type `name` exists with exactly `labels`.
+    pub fn assert_enum(&self, name: &str, labels: &[&str]) {
+        let actual = self
+            .enum_labels(name)
+            .expect("Failed to read enum labels")
+            .unwrap_or_else(|| panic!("expected enum type {} to exist", name));
+        assert_eq!(actual, labels, "labels of enum type {}", name);
+    }
+
+    /// Panic unless the composite type `name` exists with exactly `fields`,
+    /// given as `(field, type)` with types spelled like `format_type` does.
+    pub fn assert_composite(&self, name: &str, fields: &[(&str, &str)]) {
+        let actual = self
+            .composite_fields(name)
+            .expect("Failed to read composite type")
+            .unwrap_or_else(|| panic!("expected composite type {} to exist", name));
+        let actual = actual
+            .iter()
+            .map(|(field, sql_type)| (field.as_str(), sql_type.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(actual, fields, "fields of composite type {}", name);
+    }
+
+    /// Panic unless the composite type `name` exists and `value` round-trips
+    /// through it unchanged, verifying a struct's `ToSql`/`FromSql` mapping
+    /// (usually written with `WriteTuple` and `Record`) matches the type.
+    pub fn assert_composite_mapping<ST, T>(&self, name: &str, value: T)
+    where
+        ST: SqlType + SingleValue + TypedExpressionType + QueryId + 'static,
+        Pg: HasSqlType<ST>,
+        T: ToSql<ST, Pg> + FromSqlRow<ST, Pg> + PartialEq + Debug + 'static,
+    {
+        let exists = self
+            .composite_fields(name)
+            .expect("Failed to read composite type")
+            .is_some();
+        assert!(exists, "expected composite type {} to exist", name);
+        self.assert_round_trip::<ST, T>(name, value);
+    }
+
+    /// Panic unless the domain `name` exists.
+    pub fn assert_domain(&self, name: &str) {
+        let base_type = self.domain_base_type(name).expect("Failed to read domain");
+        assert!(base_type.is_some(), "expected domain {} to exist", name);
+    }
+}
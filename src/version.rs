@@ -0,0 +1,56 @@
+//! Server version checks.
+
+use diesel::{dsl::sql, select, sql_types::Text, PgConnection, QueryResult, RunQueryDsl};
+
+use crate::{TestDb, TestDbError};
+
+/// Major version of the server, e.g. `15`.
+pub(crate) fn major_version(conn: &mut PgConnection) -> QueryResult<u32> {
+    let num: String =
+        select(sql::<Text>("current_setting('server_version_num')")).get_result(conn)?;
+    Ok(num.parse::<u32>().unwrap_or(0) / 10_000)
+}
+
+/// Fail unless the server runs at least Postgres `minimum`.
+pub(crate) fn check_minimum(conn: &mut PgConnection, minimum: u32) -> Result<(), TestDbError> {
+    let actual = major_version(conn)
+        .map_err(|e| TestDbError::Config(format!("Failed to read the server version: {}", e)))?;
+    if actual < minimum {
+        return Err(TestDbError::UnsupportedVersion { actual, minimum });
+    }
+    Ok(())
+}
+
+impl TestDb {
+    /// Major version of the server the database lives on, e.g. `15`.
+    pub fn server_version(&self) -> u32 {
+        major_version(&mut self.connect())
+            .unwrap_or_else(|e| panic!("Failed to read the server version: {}", e))
+    }
+}
+
+/// Return early from the calling test, with a note on stderr, when the server
+/// of `tdb` runs a Postgres major version older than `major`.
+///
+/// ```no_run
+/// use diesel_database_tester::{require_version, TestDb};
+///
+/// let tdb = TestDb::builder().build();
+/// require_version!(tdb, 15);
+/// // MERGE is available from here on
+/// ```
+#[macro_export]
+macro_rules! require_version {
+    ($tdb:expr, $major:expr) => {
+        let actual = $tdb.server_version();
+        if actual < $major {
+            eprintln!(
+                "skipping {}: requires Postgres {}, the server runs {}",
+                module_path!(),
+                $major,
+                actual
+            );
+            return;
+        }
+    };
+}